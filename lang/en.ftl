@@ -1,8 +1,15 @@
 # Title buttons
 reset = Reset
-load-json = Load from JSON
-export-json = Export to JSON
+undo = Undo
+redo = Redo
+load-json = Import
+export-json = Export
+share-link = Share link
+export-image = Export image
+export-dot = Export DOT
+report = Report
 load-example = Load an example
+saved-networks = Saved networks
 help = Help
 github = Project on GitHub
 language = Language:
@@ -17,31 +24,102 @@ invalid-json = The provided input is not valid JSON
 err-cycle = The input graph cannot be loaded as it contains a cycle.
 err-nodenotfound = The input graph cannot be loaded as it contains references to non-existing nodes.
 err-edges = The input graph cannot be loaded as it contains duplicate edges.
+invalid-syntax = Syntax error at line {$line}
+invalid-network = The input does not describe a supported network
+unsupported-construct = The input cannot be loaded as it contains {$construct} (line {$line}), which are not supported.
+invalid-share-link = The provided link does not describe a valid network.
 loading-help = Help content is loading...
+drop-network-file = Drop a network file here, or pick one:
+paste-json = Or paste its contents:
+file-format = Format:
+download-file = Download "{$name}"
+default-network-name = network
+share-link-explanation = Anyone opening this link will get a copy of the current network:
+annotate-beliefs = Show the posterior beliefs on the nodes
+dot-show-values = List the values of the nodes
+dot-show-tables = Show the probability tables of the nodes
+report-no-parents = none
+report-posterior = Posterior beliefs:
+
+# Saved networks
+save-network-as = Save the current network as:
+no-saved-networks = No network has been saved yet.
+confirm-delete-network = Delete the saved network "{$name}"?
+storage-error = The network could not be saved in the browser, whose storage is full or disabled. Download it to keep your changes.
 
 # Generic words
 close = Close
 load = Load
+save = Save
+delete = Delete
+print = Print
 
 # Editor messages
-select-node = Select a node to edit...
+select-node = Select a node to edit, or click it in the diagram. Shift-click another node to toggle it as a parent of the selected one, drag from a node to another to add an arrow between them, and right-click an arrow to remove it.
 node-name = Node name:
 node-values = Node values:
 add-value = Add a value...
 node-parents = Node parents:
+remove-parent-marginalize = Remove, weighting the credencies by the probabilities of its values
+remove-parent-keep = Remove, keeping the credencies for {$parent} = {$value}
+reverse-parent = Reverse this arrow, making this node a parent of {$parent} while keeping the same joint probabilities
 write-desc = Write a description for this node...
 row-desc = Description for this row...
 parent-values = Parent values
 explanation = Explanation
 save-credencies = Save credencies
 default-node-name = Node #{$id}
+delete-node = Delete this node
+confirm-delete-node = Node "{$name}" still has {$children} child node(s), their credencies will be marginalized over it. Delete it anyway?
 
 # Result messages
 obs-for-nodes = Observations for nodes:
 node = Node "{$name}":
 obs-as = Observed to be: "{$value}"
+uncertain-observation = uncertain
+likelihood = Credency of each value given the uncertain evidence:
+clear-observation = clear
+excluded-values = Ruled out:
 log-odds = Log-odds
 raw-beliefs = Raw beliefs
 inference-results = Results of the inference:
 result-format = Result format:
+inference-method = Inference method:
+automatic-inference = Automatic (exact when the network is small enough)
+exact-inference = Exact (variable elimination)
+loopy-inference = Loopy belief propagation
+loopy-discrepancy = Loopy belief propagation differs from the exact result by up to {$gap}%.
+max-iterations = Maximum iterations:
+tolerance = Tolerance:
+loopy-converged = Loopy belief propagation converged after {$iterations} iteration(s).
+loopy-diverged = Loopy belief propagation did not converge after {$iterations} iteration(s) (last change: {$change}), results may be inaccurate.
+exact-skipped = The network is too large for exact inference to also be run, so the comparison with the exact results, the probability of the observations and the explanations are not available. Select exact inference to compute them anyway.
+belief-display = Show on the diagram:
+belief-bars = Probability bars
+belief-most-likely = Most likely value
+belief-hidden = Nothing
+explanations = Most probable explanations:
+explanation-count = Number of explanations:
+explained-nodes = Nodes to explain, the values of the others being summed over (all the unobserved nodes when none is checked):
+explanation-log-odds = Log-odds against the most probable
+no-explanation = The observations are impossible, nothing can explain them.
+explanations-skipped = Finding this many explanations of these nodes would take too long. Ask for fewer explanations, choose other nodes to explain, or select exact inference to find them anyway.
+evidence = Evidence:
+evidence-probability = Probability of the observations: {$probability} (decimal logarithm: {$log})
+evidence-none = No node is observed.
+evidence-impossible = The observations are impossible in this network.
+hypothesis-node = Weigh the observations for each value of the node:
+hypothesis-value = Value
+hypothesis-weight = Evidence for this value against the others
+model-comparison = Evidence given by the observations for this network against the saved ones:
+saved-network = Saved network
+model-weight = Evidence for this network
+model-incompatible = the observed nodes or values are missing from it
+scenarios = Scenarios:
+apply-scenario = Observe
+update-scenario = Replace with the current observations
+save-scenario-as = Save the current observations as a scenario:
+scenario-comparison = Comparison of the scenarios:
+scenario-node = Node
+current-observations = Current observations
 inference-no-value = Inference cannot be done if a node has no valid value.
\ No newline at end of file
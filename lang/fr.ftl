@@ -1,8 +1,15 @@
 # Title buttons
 reset = Remse à zéro
-load-json = Charger depuis du JSON
-export-json = Exporter en JSON
+undo = Annuler
+redo = Rétablir
+load-json = Importer
+export-json = Exporter
+share-link = Lien de partage
+export-image = Exporter une image
+export-dot = Exporter en DOT
+report = Rapport
 load-example = Charger un example
+saved-networks = Réseaux enregistrés
 help = Aide
 github = Projet sur GitHub
 language = Langage:
@@ -17,31 +24,102 @@ invalid-json = Le text entré n'est pas du JSON valide
 err-cycle = Le graphe n'a pas pu être chargé car il contient un cycle.
 err-nodenotfound = Le graphe n'a pas pu être chargé car il contient des références à des nœuds inexistants.
 err-edges = Le graphe n'a pas pu être chargé car il contient des arrêtes en double.
+invalid-syntax = Erreur de syntaxe à la ligne {$line}
+invalid-network = Le texte entré ne décrit pas un réseau pris en charge
+unsupported-construct = Le graphe n'a pas pu être chargé car il contient des éléments non pris en charge ({$construct}, ligne {$line}).
+invalid-share-link = Le lien fourni ne décrit pas un réseau valide.
 loading-help = Le contenu de l'aide est en chargement...
+drop-network-file = Déposez un fichier de réseau ici, ou choisissez-en un :
+paste-json = Ou collez son contenu :
+file-format = Format :
+download-file = Télécharger « {$name} »
+default-network-name = réseau
+share-link-explanation = Toute personne ouvrant ce lien obtiendra une copie du réseau actuel :
+annotate-beliefs = Afficher les croyances a posteriori sur les nœuds
+dot-show-values = Lister les valeurs des nœuds
+dot-show-tables = Afficher les tables de probabilités des nœuds
+report-no-parents = aucun
+report-posterior = Croyances a posteriori :
+
+# Saved networks
+save-network-as = Enregistrer le réseau actuel sous le nom :
+no-saved-networks = Aucun réseau n'a encore été enregistré.
+confirm-delete-network = Supprimer le réseau enregistré « {$name} » ?
+storage-error = Le réseau n'a pas pu être enregistré dans le navigateur, dont le stockage est plein ou désactivé. Téléchargez-le pour conserver vos modifications.
 
 # Generic words
 close = Fermer
 load = Charger
+save = Enregistrer
+delete = Supprimer
+print = Imprimer
 
 # Editor messages
-select-node = Choisissez un nœud à modifier...
+select-node = Choisissez un nœud à modifier, ou cliquez dessus dans le diagramme. Cliquez sur un autre nœud en maintenant Maj pour l'ajouter ou le retirer des parents du nœud sélectionné, glissez d'un nœud à un autre pour ajouter une flèche entre eux, et faites un clic droit sur une flèche pour la supprimer.
 node-name = Nom du nœud:
 node-values = Valeurs du nœud:
 add-value = Ajouter une valeur...
 node-parents = Parents du nœud:
+remove-parent-marginalize = Retirer, en pondérant les crédences par les probabilités de ses valeurs
+remove-parent-keep = Retirer, en gardant les crédences pour {$parent} = {$value}
+reverse-parent = Inverser cette flèche, pour que ce nœud devienne un parent de {$parent} en gardant les mêmes probabilités jointes
 write-desc = Écrivez une description pour ce nœud...
 row-desc = Description pour cette ligne...
 parent-values = Valeurs parentes
 explanation = Explication
 save-credencies = Enregistrer les crédences
 default-node-name = Nœud #{$id}
+delete-node = Supprimer ce nœud
+confirm-delete-node = Le nœud « {$name} » a encore {$children} nœud(s) enfant(s), leurs crédences seront marginalisées sur ses valeurs. Le supprimer quand même ?
 
 # Result messages
 obs-for-nodes = Observations pour les nœuds:
 node = Nœud « {$name} » :
 obs-as = Observé comme étant : « {$value} »
+uncertain-observation = incertaine
+likelihood = Crédence de chaque valeur selon l'observation incertaine :
+clear-observation = effacer
+excluded-values = Valeurs exclues :
 log-odds = Log-cote
 raw-beliefs = Croyances brutes
 inference-results = Résultats de l'inférence :
 result-format = Format de résultats :
+inference-method = Méthode d'inférence :
+automatic-inference = Automatique (exacte si le réseau est assez petit)
+exact-inference = Exacte (élimination de variables)
+loopy-inference = Propagation de croyances en boucle
+loopy-discrepancy = La propagation de croyances en boucle diffère du résultat exact d'au plus {$gap} %.
+max-iterations = Nombre maximal d'itérations :
+tolerance = Tolérance :
+loopy-converged = La propagation de croyances en boucle a convergé après {$iterations} itération(s).
+loopy-diverged = La propagation de croyances en boucle n'a pas convergé après {$iterations} itération(s) (dernier changement : {$change}), les résultats peuvent être imprécis.
+exact-skipped = Le réseau est trop grand pour que l'inférence exacte soit lancée en plus, la comparaison avec les résultats exacts, la probabilité des observations et les explications ne sont donc pas disponibles. Choisissez l'inférence exacte pour les calculer malgré tout.
+belief-display = Afficher sur le diagramme :
+belief-bars = Barres de probabilités
+belief-most-likely = Valeur la plus probable
+belief-hidden = Rien
+explanations = Explications les plus probables :
+explanation-count = Nombre d'explications :
+explained-nodes = Nœuds à expliquer, en sommant sur les valeurs des autres (tous les nœuds non observés si aucun n'est coché) :
+explanation-log-odds = Log-cote par rapport à la plus probable
+no-explanation = Les observations sont impossibles, rien ne peut les expliquer.
+explanations-skipped = Trouver autant d'explications de ces nœuds prendrait trop de temps. Demandez moins d'explications, choisissez d'autres nœuds à expliquer, ou choisissez l'inférence exacte pour les trouver malgré tout.
+evidence = Preuves :
+evidence-probability = Probabilité des observations : {$probability} (logarithme décimal : {$log})
+evidence-none = Aucun nœud n'est observé.
+evidence-impossible = Les observations sont impossibles dans ce réseau.
+hypothesis-node = Peser les observations pour chaque valeur du nœud :
+hypothesis-value = Valeur
+hypothesis-weight = Preuve en faveur de cette valeur contre les autres
+model-comparison = Preuve apportée par les observations en faveur de ce réseau contre ceux enregistrés :
+saved-network = Réseau enregistré
+model-weight = Preuve en faveur de ce réseau
+model-incompatible = les nœuds ou valeurs observés en sont absents
+scenarios = Scénarios :
+apply-scenario = Observer
+update-scenario = Remplacer par les observations actuelles
+save-scenario-as = Enregistrer les observations actuelles comme un scénario :
+scenario-comparison = Comparaison des scénarios :
+scenario-node = Nœud
+current-observations = Observations actuelles
 inference-no-value = L'inférence ne peut pas être effectuée si un nœud n'a pas de valeur valide.
\ No newline at end of file
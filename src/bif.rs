@@ -0,0 +1,647 @@
+use std::collections::HashMap;
+
+use ndarray::{ArrayD, IxDyn};
+
+use crate::formats::{
+    format_probability, make_identifier, probabilities_to_credencies, value_combinations,
+};
+use crate::graph::{DeserError, DAG};
+
+/*
+ * Import and export of the BIF interchange format
+ */
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    Word(String),
+    Str(String),
+    Punct(char),
+}
+
+/// Splits a text into words, quoted strings and punctuation, skipping whitespace
+/// and comments
+pub struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    punctuation: &'static str,
+    line_comment: &'static str,
+}
+
+impl<'a> Lexer<'a> {
+    /// Creates a lexer for a language using the given punctuation characters, and whose
+    /// comments run from `line_comment` to the end of the line or are C-style
+    pub fn new(text: &'a str, punctuation: &'static str, line_comment: &'static str) -> Lexer<'a> {
+        Lexer {
+            chars: text.chars().peekable(),
+            line: 1,
+            punctuation,
+            line_comment,
+        }
+    }
+
+    /// The line the lexer is currently at
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn error<T>(&self, message: String) -> Result<T, DeserError> {
+        Err(DeserError::Syntax {
+            line: self.line,
+            message,
+        })
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        c
+    }
+
+    /// Whether the remaining text starts with `prefix`
+    fn at(&self, prefix: &str) -> bool {
+        let mut chars = self.chars.clone();
+        prefix.chars().all(|c| chars.next() == Some(c))
+    }
+
+    fn skip_blanks(&mut self) -> Result<(), DeserError> {
+        loop {
+            if self.chars.clone().next().map(char::is_whitespace) == Some(true) {
+                self.bump();
+            } else if self.at(self.line_comment) {
+                while self.bump().map(|c| c != '\n').unwrap_or(false) {}
+            } else if self.at("/*") {
+                self.bump();
+                self.bump();
+                let mut star = false;
+                loop {
+                    match self.bump() {
+                        Some('/') if star => break,
+                        Some(c) => star = c == '*',
+                        None => return self.error("unterminated comment".into()),
+                    }
+                }
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    pub fn next_token(&mut self) -> Result<Option<Token>, DeserError> {
+        self.skip_blanks()?;
+        let c = match self.bump() {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        if c == '"' {
+            let mut s = String::new();
+            loop {
+                match self.bump() {
+                    Some('"') => return Ok(Some(Token::Str(s))),
+                    Some('\\') => match self.bump() {
+                        Some('n') => s.push('\n'),
+                        Some(c) => s.push(c),
+                        None => break,
+                    },
+                    Some(c) => s.push(c),
+                    None => break,
+                }
+            }
+            return self.error("unterminated string".into());
+        }
+        if self.punctuation.contains(c) {
+            return Ok(Some(Token::Punct(c)));
+        }
+        let mut word = c.to_string();
+        while let Some(&c) = self.chars.peek() {
+            if c.is_whitespace() || c == '"' || self.punctuation.contains(c) {
+                break;
+            }
+            word.push(c);
+            self.bump();
+        }
+        Ok(Some(Token::Word(word)))
+    }
+
+    /// Returns the next token, failing at the end of the input
+    pub fn token(&mut self) -> Result<Token, DeserError> {
+        match self.next_token()? {
+            Some(t) => Ok(t),
+            None => self.error("unexpected end of file".into()),
+        }
+    }
+
+    pub fn expect(&mut self, punct: char) -> Result<(), DeserError> {
+        match self.token()? {
+            Token::Punct(c) if c == punct => Ok(()),
+            t => self.error(format!("expected '{}', found {}", punct, describe(&t))),
+        }
+    }
+
+    /// Reads a name, either as a bare word or as a quoted string
+    pub fn name(&mut self) -> Result<String, DeserError> {
+        match self.token()? {
+            Token::Word(w) | Token::Str(w) => Ok(w),
+            t => self.error(format!("expected a name, found {}", describe(&t))),
+        }
+    }
+
+    pub fn number(&mut self) -> Result<f32, DeserError> {
+        match self.token()? {
+            Token::Word(ref w) => match w.parse() {
+                Ok(v) => Ok(v),
+                Err(_) => self.error(format!("expected a number, found '{}'", w)),
+            },
+            t => self.error(format!("expected a number, found {}", describe(&t))),
+        }
+    }
+
+    /// Reads the tokens up to the closing delimiter of a block whose opening delimiter
+    /// was already read
+    pub fn skip_block(&mut self, open: char, close: char) -> Result<(), DeserError> {
+        let mut depth = 1;
+        while depth > 0 {
+            match self.token()? {
+                Token::Punct(c) if c == open => depth += 1,
+                Token::Punct(c) if c == close => depth -= 1,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn describe(token: &Token) -> String {
+    match token {
+        Token::Word(w) => format!("'{}'", w),
+        Token::Str(s) => format!("\"{}\"", s),
+        Token::Punct(c) => format!("'{}'", c),
+    }
+}
+
+/// Quotes a string, escaping it as needed
+pub fn quote(text: &str) -> String {
+    format!(
+        "\"{}\"",
+        text.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    )
+}
+
+const BIF_PUNCTUATION: &str = "{}()[],;|=";
+
+/// Writes the name of a value of a node, as is if it can be read as a word and quoted
+/// otherwise, distinct from the names of the previous values in `taken`
+fn value_name(value: &str, taken: &mut Vec<String>) -> String {
+    if taken.iter().any(|t| t == value) {
+        return make_identifier(value, "value", taken);
+    }
+    taken.push(value.into());
+    let plain = value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.');
+    if plain && !value.is_empty() {
+        value.into()
+    } else {
+        quote(value)
+    }
+}
+
+struct BifVariable {
+    name: String,
+    label: Option<String>,
+    description: String,
+    values: Vec<String>,
+}
+
+struct BifProbability {
+    line: usize,
+    child: String,
+    parents: Vec<String>,
+    /// The probabilities of the values of the child, for combinations of the values
+    /// of the parents
+    rows: Vec<(Vec<String>, Vec<f32>)>,
+    table: Option<Vec<f32>>,
+    default: Option<Vec<f32>>,
+}
+
+/// Reads numbers separated by commas or whitespace, up to a semicolon
+fn parse_numbers(lexer: &mut Lexer) -> Result<Vec<f32>, DeserError> {
+    let mut numbers = Vec::new();
+    loop {
+        match lexer.token()? {
+            Token::Punct(';') => return Ok(numbers),
+            Token::Punct(',') => {}
+            Token::Word(ref w) => match w.parse() {
+                Ok(v) => numbers.push(v),
+                Err(_) => return lexer.error(format!("expected a number, found '{}'", w)),
+            },
+            t => return lexer.error(format!("expected a number, found {}", describe(&t))),
+        }
+    }
+}
+
+/// Reads a parenthesized list of names whose opening parenthesis was already read,
+/// returning the names before and after an optional '|'
+fn parse_name_list(lexer: &mut Lexer) -> Result<(Vec<String>, Vec<String>), DeserError> {
+    let mut before = Vec::new();
+    let mut after = Vec::new();
+    let mut conditioned = false;
+    loop {
+        match lexer.token()? {
+            Token::Punct(')') => return Ok((before, after)),
+            Token::Punct(',') => {}
+            Token::Punct('|') => conditioned = true,
+            Token::Word(w) | Token::Str(w) => {
+                if conditioned {
+                    after.push(w)
+                } else {
+                    before.push(w)
+                }
+            }
+            t => return lexer.error(format!("expected a name, found {}", describe(&t))),
+        }
+    }
+}
+
+/// Reads a property whose keyword was already read, returning its key and value if
+/// it is of the form `key = "value"`
+fn parse_property(lexer: &mut Lexer) -> Result<Option<(String, String)>, DeserError> {
+    let mut tokens = Vec::new();
+    loop {
+        match lexer.token()? {
+            Token::Punct(';') => break,
+            t => tokens.push(t),
+        }
+    }
+    match &tokens[..] {
+        [Token::Word(key), Token::Punct('='), Token::Str(value)] => {
+            Ok(Some((key.clone(), value.clone())))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn parse_variable(lexer: &mut Lexer) -> Result<BifVariable, DeserError> {
+    let mut variable = BifVariable {
+        name: lexer.name()?,
+        label: None,
+        description: String::new(),
+        values: Vec::new(),
+    };
+    lexer.expect('{')?;
+    loop {
+        match lexer.token()? {
+            Token::Punct('}') => break,
+            Token::Word(ref w) if w == "type" => {
+                match lexer.token()? {
+                    Token::Word(ref w) if w == "discrete" => {}
+                    t => {
+                        return lexer.error(format!(
+                            "variable '{}' is not discrete: {}",
+                            variable.name,
+                            describe(&t)
+                        ))
+                    }
+                }
+                lexer.expect('[')?;
+                let count = lexer.number()?;
+                lexer.expect(']')?;
+                lexer.expect('{')?;
+                loop {
+                    match lexer.token()? {
+                        Token::Punct('}') => break,
+                        Token::Punct(',') => {}
+                        Token::Word(w) | Token::Str(w) => variable.values.push(w),
+                        t => {
+                            return lexer.error(format!("expected a value, found {}", describe(&t)))
+                        }
+                    }
+                }
+                if variable.values.len() != count as usize {
+                    return lexer.error(format!(
+                        "variable '{}' declares {} values but lists {}",
+                        variable.name,
+                        count,
+                        variable.values.len()
+                    ));
+                }
+                lexer.expect(';')?;
+            }
+            Token::Word(ref w) if w == "property" => match parse_property(lexer)? {
+                Some((ref key, value)) if key == "label" => variable.label = Some(value),
+                Some((ref key, value)) if key == "description" => variable.description = value,
+                _ => {}
+            },
+            t => return lexer.error(format!("unexpected {} in variable", describe(&t))),
+        }
+    }
+    Ok(variable)
+}
+
+fn parse_probability(lexer: &mut Lexer) -> Result<BifProbability, DeserError> {
+    let line = lexer.line();
+    lexer.expect('(')?;
+    let (mut child, parents) = parse_name_list(lexer)?;
+    if child.len() != 1 {
+        return lexer.error("a probability block must be about exactly one variable".into());
+    }
+    let mut probability = BifProbability {
+        line,
+        child: child.remove(0),
+        parents,
+        rows: Vec::new(),
+        table: None,
+        default: None,
+    };
+    lexer.expect('{')?;
+    loop {
+        match lexer.token()? {
+            Token::Punct('}') => break,
+            Token::Punct('(') => {
+                let (values, _) = parse_name_list(lexer)?;
+                probability.rows.push((values, parse_numbers(lexer)?));
+            }
+            Token::Word(ref w) if w == "table" => probability.table = Some(parse_numbers(lexer)?),
+            Token::Word(ref w) if w == "default" => {
+                probability.default = Some(parse_numbers(lexer)?)
+            }
+            Token::Word(ref w) if w == "property" => {
+                parse_property(lexer)?;
+            }
+            t => return lexer.error(format!("unexpected {} in probability", describe(&t))),
+        }
+    }
+    Ok(probability)
+}
+
+/// Builds the probability table of a node, of shape `[child values, parent values...]`
+fn make_table(
+    probability: &BifProbability,
+    variables: &[BifVariable],
+    ids: &[usize],
+) -> Result<ArrayD<f32>, DeserError> {
+    let error = |message: String| {
+        Err(DeserError::Syntax {
+            line: probability.line,
+            message,
+        })
+    };
+    let child = &variables[ids[0]];
+    let parents = ids[1..].iter().map(|&i| &variables[i]).collect::<Vec<_>>();
+    if child.values.is_empty() {
+        return error(format!("variable '{}' has no values", child.name));
+    }
+    let mut shape = vec![child.values.len()];
+    shape.extend(parents.iter().map(|p| p.values.len()));
+    let mut table = ArrayD::from_elem(IxDyn(&shape), std::f32::NAN);
+    let set_column = |table: &mut ArrayD<f32>, combination: &[usize], values: &[f32]| {
+        for (i, &v) in values.iter().enumerate() {
+            let mut index = vec![i];
+            index.extend(combination);
+            table[IxDyn(&index)] = v;
+        }
+    };
+
+    if let Some(ref values) = probability.default {
+        if values.len() != child.values.len() {
+            return error(format!(
+                "wrong number of default values for '{}'",
+                child.name
+            ));
+        }
+        for combination in value_combinations(&shape[1..]) {
+            set_column(&mut table, &combination, values);
+        }
+    }
+    if let Some(ref values) = probability.table {
+        if values.len() != table.len() {
+            return error(format!("wrong number of table values for '{}'", child.name));
+        }
+        // the values are listed with the child varying slowest, like the credencies
+        table = ArrayD::from_shape_vec(IxDyn(&shape), values.clone()).unwrap();
+    }
+    for (values, row) in &probability.rows {
+        if values.len() != parents.len() || row.len() != child.values.len() {
+            return error(format!("wrong number of values for '{}'", child.name));
+        }
+        let mut combination = Vec::new();
+        for (value, parent) in values.iter().zip(&parents) {
+            match parent.values.iter().position(|v| v == value) {
+                Some(i) => combination.push(i),
+                None => return error(format!("'{}' is not a value of '{}'", value, parent.name)),
+            }
+        }
+        set_column(&mut table, &combination, row);
+    }
+    if table.iter().any(|v| v.is_nan()) {
+        return error(format!("incomplete probability table for '{}'", child.name));
+    }
+    Ok(table)
+}
+
+impl DAG {
+    pub fn from_bif(text: &str) -> Result<DAG, DeserError> {
+        let mut lexer = Lexer::new(text, BIF_PUNCTUATION, "//");
+        let mut variables = Vec::new();
+        let mut probabilities = Vec::new();
+        while let Some(token) = lexer.next_token()? {
+            match token {
+                Token::Word(ref w) if w == "network" => {
+                    lexer.name()?;
+                    lexer.expect('{')?;
+                    lexer.skip_block('{', '}')?;
+                }
+                Token::Word(ref w) if w == "variable" => {
+                    variables.push(parse_variable(&mut lexer)?)
+                }
+                Token::Word(ref w) if w == "probability" => {
+                    probabilities.push(parse_probability(&mut lexer)?)
+                }
+                t => return lexer.error(format!("unexpected {}", describe(&t))),
+            }
+        }
+
+        let mut dag = DAG::new();
+        for variable in &variables {
+            let id = dag.insert_node();
+            dag.set_label(
+                id,
+                variable
+                    .label
+                    .clone()
+                    .unwrap_or_else(|| variable.name.clone()),
+            );
+            dag.set_description(id, variable.description.clone());
+            for value in &variable.values {
+                dag.add_value(id, value.clone());
+            }
+        }
+        for probability in &probabilities {
+            let mut ids = Vec::new();
+            for name in Some(&probability.child)
+                .into_iter()
+                .chain(&probability.parents)
+            {
+                match variables.iter().position(|v| &v.name == name) {
+                    Some(id) => ids.push(id),
+                    None => {
+                        return Err(DeserError::Syntax {
+                            line: probability.line,
+                            message: format!("unknown variable '{}'", name),
+                        })
+                    }
+                }
+            }
+            for &parent in &ids[1..] {
+                dag.add_edge(ids[0], parent).map_err(DeserError::Graph)?;
+            }
+            let table = make_table(probability, &variables, &ids)?;
+            dag.set_credencies(ids[0], probabilities_to_credencies(&table))
+                .map_err(|()| DeserError::Syntax {
+                    line: probability.line,
+                    message: format!("inconsistent table for '{}'", probability.child),
+                })?;
+        }
+        Ok(dag)
+    }
+
+    pub fn to_bif(&self) -> String {
+        let order = self.topological_order();
+        let mut taken = Vec::new();
+        let names = self
+            .iter_nodes()
+            .map(|(id, node)| (id, make_identifier(&node.label, "node", &mut taken)))
+            .collect::<HashMap<_, _>>();
+
+        let mut bif = String::from("network unknown {\n}\n");
+        let mut value_names = HashMap::new();
+        for &id in &order {
+            let node = self.get(id).unwrap();
+            let mut taken = Vec::new();
+            let values = node
+                .values
+                .iter()
+                .map(|v| value_name(v, &mut taken))
+                .collect::<Vec<_>>();
+            bif += &format!("variable {} {{\n", names[&id]);
+            bif += &format!(
+                "  type discrete [ {} ] {{ {} }};\n",
+                values.len(),
+                values.join(", ")
+            );
+            if node.label != names[&id] {
+                bif += &format!("  property label = {} ;\n", quote(&node.label));
+            }
+            if !node.description.is_empty() {
+                bif += &format!("  property description = {} ;\n", quote(&node.description));
+            }
+            bif += "}\n";
+            value_names.insert(id, values);
+        }
+        for &id in &order {
+            let node = self.get(id).unwrap();
+            let probabilities = self.probability_table(id).unwrap();
+            let shape = probabilities.shape().to_vec();
+            let column = |combination: &[usize]| {
+                (0..node.values.len())
+                    .map(|i| {
+                        let mut index = vec![i];
+                        index.extend(combination);
+                        format_probability(probabilities[IxDyn(&index)])
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            if node.parents.is_empty() {
+                bif += &format!("probability ( {} ) {{\n", names[&id]);
+                bif += &format!("  table {};\n", column(&[]));
+            } else {
+                let parents = node
+                    .parents
+                    .iter()
+                    .map(|p| &names[p][..])
+                    .collect::<Vec<_>>();
+                bif += &format!(
+                    "probability ( {} | {} ) {{\n",
+                    names[&id],
+                    parents.join(", ")
+                );
+                for combination in value_combinations(&shape[1..]) {
+                    let values = combination
+                        .iter()
+                        .zip(&node.parents)
+                        .map(|(&v, p)| &value_names[p][v][..])
+                        .collect::<Vec<_>>();
+                    bif += &format!("  ({}) {};\n", values.join(", "), column(&combination));
+                }
+            }
+            bif += "}\n";
+        }
+        bif
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::credencies_to_probabilities;
+    use crate::formats::tests::{assert_same_network, EXAMPLES};
+
+    #[test]
+    fn round_trip_examples() {
+        for json in EXAMPLES {
+            let dag = DAG::from_json(json).unwrap();
+            let copy = DAG::from_bif(&dag.to_bif()).unwrap();
+            assert_same_network(&dag, &copy, true);
+        }
+    }
+
+    #[test]
+    fn quoted_values() {
+        let mut dag = DAG::new();
+        let a = dag.insert_node();
+        dag.set_label(a, "a".into());
+        for value in &["Not sure", "yes", "yes", "l'été"] {
+            dag.add_value(a, value.to_string());
+        }
+        let bif = dag.to_bif();
+        assert!(bif.contains("{ \"Not sure\", yes, yes_2, \"l'été\" }"));
+        let copy = DAG::from_bif(&bif).unwrap();
+        let (_, node) = copy.iter_nodes().next().unwrap();
+        assert_eq!(node.values, vec!["Not sure", "yes", "yes_2", "l'été"]);
+    }
+
+    #[test]
+    fn table_child_slowest() {
+        let bif = "network dog { }
+            variable fo { type discrete [ 2 ] { t, f }; }
+            variable lo { type discrete [ 2 ] { t, f }; }
+            probability ( fo ) { table 0.15 0.85; }
+            probability ( lo | fo ) { table 0.6 0.05 0.4 0.95; }";
+        let dag = DAG::from_bif(bif).unwrap();
+        let (_, lo) = dag.iter_nodes().find(|(_, n)| n.label == "lo").unwrap();
+        let table = credencies_to_probabilities(lo.credencies.as_ref().unwrap());
+        // P(lo = t | fo = f)
+        assert!((table[IxDyn(&[0, 1])] - 0.05).abs() < 1e-6);
+        // P(lo = f | fo = t)
+        assert!((table[IxDyn(&[1, 0])] - 0.4).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rows_and_default() {
+        let bif = "network unknown { }
+            variable a { type discrete [ 2 ] { x, y }; }
+            variable b { type discrete [ 3 ] { p, q, r }; }
+            probability ( a ) { table 0.5, 0.5; }
+            probability ( b | a ) { default 0.2, 0.3, 0.5; (y) 0.1, 0.1, 0.8; }";
+        let dag = DAG::from_bif(bif).unwrap();
+        let (_, b) = dag.iter_nodes().find(|(_, n)| n.label == "b").unwrap();
+        let table = credencies_to_probabilities(b.credencies.as_ref().unwrap());
+        assert!((table[IxDyn(&[1, 0])] - 0.3).abs() < 1e-6);
+        assert!((table[IxDyn(&[2, 1])] - 0.8).abs() < 1e-6);
+        assert!(DAG::from_bif("variable a { type discrete [ 2 ] { x }; }").is_err());
+    }
+}
@@ -1,28 +1,126 @@
+use crate::formats::value_combinations;
 use crate::graph::DAG;
+use std::cell::RefCell;
 use std::fmt::Write;
+use std::rc::Rc;
 
-use stdweb::js;
+use loopybayesnet::LogProbVector;
+use ndarray::IxDyn;
+use stdweb::{js, Value};
 use yew::{
-    html, virtual_dom::vnode::VNode, Component, ComponentLink, Html, Properties, Renderable,
-    ShouldRender,
+    html, virtual_dom::vnode::VNode, Callback, Component, ComponentLink, Html, Properties,
+    Renderable, ShouldRender,
 };
 
-pub fn graph_to_dot(graph: &DAG) -> String {
+/// How the posterior beliefs of the nodes are written on the diagram
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BeliefDisplay {
+    Hidden,
+    /// A probability bar for each value
+    Bars,
+    /// Only the most likely value and its probability
+    MostLikely,
+}
+
+/// Inference results to show on the nodes of the diagram
+pub struct BeliefAnnotations<'a> {
+    pub beliefs: &'a [(LogProbVector, usize)],
+    /// The beliefs without any observation, to color the nodes by how much the
+    /// observations moved them
+    pub priors: Option<&'a [(LogProbVector, usize)]>,
+    pub display: BeliefDisplay,
+}
+
+/// Number of characters of the probability bars
+const BAR_LENGTH: usize = 10;
+
+fn probabilities_of(beliefs: &[(LogProbVector, usize)], node: usize) -> Option<Vec<f32>> {
+    beliefs
+        .iter()
+        .find(|&&(_, id)| id == node)
+        .map(|&(ref b, _)| b.as_probabilities().to_vec())
+}
+
+/// Describes the graph for dagre-d3, optionally annotating the nodes with their
+/// posterior beliefs
+pub fn graph_to_dot(graph: &DAG, annotations: Option<&BeliefAnnotations>) -> String {
     let mut buffer = String::new();
     writeln!(buffer, "digraph {{").unwrap();
     writeln!(buffer, "node [rx=16 ry=16]").unwrap();
     for (id, node) in graph.iter_nodes() {
         let mut style = String::new();
-        if node.observation.is_some() {
+        if node.observation.is_some() || node.allowed.is_some() {
             style.push_str("font-weight: bold;");
         }
+        if node.likelihood.is_some() {
+            style.push_str("font-style: italic;");
+        }
         if node.values.is_empty() {
             style.push_str("fill: #d00;");
         }
+        let mut label = node.label.clone();
+        let mut shape_style = String::new();
+        let posterior = annotations.and_then(|a| probabilities_of(a.beliefs, id));
+        if let (Some(annotations), Some(posterior)) = (annotations, posterior) {
+            match annotations.display {
+                BeliefDisplay::Hidden => {}
+                BeliefDisplay::Bars => {
+                    for (value, &p) in node.values.iter().zip(&posterior) {
+                        let filled = (p * BAR_LENGTH as f32).round() as usize;
+                        write!(
+                            label,
+                            "\\n{}{} {:.0}% {}",
+                            "\u{2588}".repeat(filled),
+                            "\u{2591}".repeat(BAR_LENGTH - filled),
+                            p * 100.0,
+                            value
+                        )
+                        .unwrap();
+                    }
+                }
+                BeliefDisplay::MostLikely => {
+                    let best = (0..posterior.len())
+                        .max_by(|&a, &b| posterior[a].partial_cmp(&posterior[b]).unwrap());
+                    if let Some(best) = best {
+                        write!(
+                            label,
+                            "\\n{} ({:.0}%)",
+                            node.values[best],
+                            posterior[best] * 100.0
+                        )
+                        .unwrap();
+                    }
+                }
+            }
+            // the more the observations moved the beliefs of a node, the more orange it is
+            let prior = annotations.priors.and_then(|p| probabilities_of(p, id));
+            if let (None, Some(prior)) = (node.observation, prior) {
+                let shift = 0.5
+                    * posterior
+                        .iter()
+                        .zip(&prior)
+                        .map(|(p, q)| (p - q).abs())
+                        .sum::<f32>();
+                let shift = shift.min(1.0).max(0.0);
+                write!(
+                    shape_style,
+                    "fill: #ff{:02x}{:02x};",
+                    (255.0 - 90.0 * shift) as u8,
+                    (255.0 - 255.0 * shift) as u8
+                )
+                .unwrap();
+            }
+        }
+        if node.likelihood.is_some() {
+            shape_style.push_str("stroke-dasharray: 5 3;");
+        }
         writeln!(
             buffer,
-            "n{} [label=\"{}\" labelStyle=\"{}\"];",
-            id, node.label, style
+            "n{} [label=\"{}\" labelStyle=\"{}\" style=\"{}\"];",
+            id,
+            label.replace('"', "\\\""),
+            style,
+            shape_style
         )
         .unwrap();
     }
@@ -37,32 +135,235 @@ pub fn graph_to_dot(graph: &DAG) -> String {
     buffer
 }
 
+/// What to show in the nodes of a Graphviz export, besides their labels
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GraphvizOptions {
+    /// List the values of the nodes
+    pub values: bool,
+    /// Show the probability tables of the nodes
+    pub tables: bool,
+}
+
+/// Escapes text for use in a quoted Graphviz string
+fn escape_quoted(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Escapes text for use in a Graphviz HTML-like label
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\n', "<BR/>")
+}
+
+/// Builds the HTML-like label of a node, as a table of its values and probabilities
+fn graphviz_html_label(graph: &DAG, id: usize, options: GraphvizOptions) -> String {
+    let node = graph.get(id).unwrap();
+    let parents = node
+        .parents
+        .iter()
+        .map(|&p| graph.get(p).unwrap())
+        .collect::<Vec<_>>();
+    let columns = if options.tables { parents.len() } else { 0 } + node.values.len().max(1);
+    let mut label = String::from("<<TABLE BORDER=\"0\" CELLBORDER=\"1\" CELLSPACING=\"0\">");
+    write!(
+        label,
+        "<TR><TD COLSPAN=\"{}\"><B>{}</B></TD></TR>",
+        columns,
+        escape_html(&node.label)
+    )
+    .unwrap();
+
+    let value_cells = node
+        .values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| {
+            if node.observation == Some(i) {
+                format!("<TD><U>{}</U></TD>", escape_html(v))
+            } else if node.allowed.as_ref().map_or(false, |a| !a[i]) {
+                format!("<TD><S>{}</S></TD>", escape_html(v))
+            } else {
+                format!("<TD>{}</TD>", escape_html(v))
+            }
+        })
+        .collect::<String>();
+    label.push_str("<TR>");
+    if options.tables {
+        for parent in &parents {
+            write!(label, "<TD><I>{}</I></TD>", escape_html(&parent.label)).unwrap();
+        }
+    }
+    label.push_str(&value_cells);
+    label.push_str("</TR>");
+
+    if options.tables && !node.values.is_empty() {
+        let probabilities = graph.probability_table(id).unwrap();
+        for combination in value_combinations(&probabilities.shape()[1..]) {
+            label.push_str("<TR>");
+            for (parent, &v) in parents.iter().zip(&combination) {
+                write!(label, "<TD>{}</TD>", escape_html(&parent.values[v])).unwrap();
+            }
+            for i in 0..node.values.len() {
+                let mut index = vec![i];
+                index.extend(&combination);
+                write!(label, "<TD>{:.3}</TD>", probabilities[IxDyn(&index)]).unwrap();
+            }
+            label.push_str("</TR>");
+        }
+    }
+    label.push_str("</TABLE>>");
+    label
+}
+
+/// Describes the graph in the Graphviz DOT language, for use with external tools
+pub fn graph_to_graphviz(graph: &DAG, options: GraphvizOptions) -> String {
+    let mut buffer = String::new();
+    writeln!(buffer, "digraph {{").unwrap();
+    writeln!(buffer, "    node [shape=box, style=rounded];").unwrap();
+    for (id, node) in graph.iter_nodes() {
+        let label = if options.values || options.tables {
+            graphviz_html_label(graph, id, options)
+        } else {
+            format!("\"{}\"", escape_quoted(&node.label))
+        };
+        let mut attributes = format!("label={}", label);
+        if node.observation.is_some() || node.allowed.is_some() {
+            attributes.push_str(", penwidth=3");
+        }
+        if node.likelihood.is_some() {
+            attributes.push_str(", style=\"rounded,dashed\"");
+        }
+        if node.values.is_empty() {
+            attributes.push_str(", style=\"rounded,filled\", fillcolor=\"#dd0000\"");
+        }
+        writeln!(buffer, "    n{} [{}];", id, attributes).unwrap();
+    }
+
+    for (id, node) in graph.iter_nodes() {
+        for parent in &node.parents {
+            writeln!(buffer, "    n{} -> n{};", parent, id).unwrap();
+        }
+    }
+
+    writeln!(buffer, "}}").unwrap();
+    buffer
+}
+
+/// Something the user did on the diagram
+#[derive(Copy, Clone, Debug)]
+pub enum CanvasEvent {
+    /// A node was clicked, with shift held or not
+    Click { node: usize, shift: bool },
+    /// An edge was dragged from `parent` to `child`
+    Connect { parent: usize, child: usize },
+    /// The edge from `parent` to `child` was right-clicked
+    Disconnect { parent: usize, child: usize },
+}
+
+/// Parses the "n{id}" identifiers the nodes have in the DOT source
+fn node_id(name: &str) -> Option<usize> {
+    name.trim_start_matches('n').parse().ok()
+}
+
 pub struct DotCanvas {
     dot: String,
+    selected: Option<usize>,
+    /// Copy of the drawn graph, to check the edges dragged by the user
+    graph: Rc<RefCell<DAG>>,
+    onevent: Callback<CanvasEvent>,
+    /// The javascript functions the diagram calls back into
+    handlers: Value,
 }
 
-#[derive(PartialEq, Properties)]
+#[derive(Properties)]
 pub struct Props {
     #[props(required)]
     pub dot: String,
+    #[props(required)]
+    pub graph: DAG,
+    /// The node to highlight in the diagram
+    pub selected: Option<usize>,
+    #[props(required)]
+    pub onevent: Callback<CanvasEvent>,
 }
 
 impl Component for DotCanvas {
-    type Message = ();
+    type Message = CanvasEvent;
     type Properties = Props;
 
-    fn create(props: Props, _: ComponentLink<Self>) -> Self {
-        DotCanvas { dot: props.dot }
+    fn create(props: Props, mut link: ComponentLink<Self>) -> Self {
+        let callback = link.send_back(|event| event);
+        let graph = Rc::new(RefCell::new(props.graph));
+
+        let click_callback = callback.clone();
+        let click = move |name: String, shift: bool| {
+            if let Some(node) = node_id(&name) {
+                click_callback.emit(CanvasEvent::Click { node, shift });
+            }
+        };
+        let connect_callback = callback.clone();
+        let connect = move |parent: String, child: String| {
+            if let (Some(parent), Some(child)) = (node_id(&parent), node_id(&child)) {
+                connect_callback.emit(CanvasEvent::Connect { parent, child });
+            }
+        };
+        let disconnect = move |parent: String, child: String| {
+            if let (Some(parent), Some(child)) = (node_id(&parent), node_id(&child)) {
+                callback.emit(CanvasEvent::Disconnect { parent, child });
+            }
+        };
+        let checked_graph = graph.clone();
+        let check = move |parent: String, child: String| -> bool {
+            match (node_id(&parent), node_id(&child)) {
+                (Some(parent), Some(child)) => checked_graph
+                    .borrow()
+                    .check_edge_addition(child, parent)
+                    .is_ok(),
+                _ => false,
+            }
+        };
+        let handlers = js! {
+            return {
+                click: @{click},
+                connect: @{connect},
+                disconnect: @{disconnect},
+                check: @{check},
+            };
+        };
+        DotCanvas {
+            dot: props.dot,
+            selected: props.selected,
+            onevent: props.onevent,
+            graph,
+            handlers,
+        }
     }
 
-    fn update(&mut self, _msg: ()) -> ShouldRender {
-        true
+    fn update(&mut self, event: CanvasEvent) -> ShouldRender {
+        self.onevent.emit(event);
+        false
     }
 
     fn change(&mut self, props: Props) -> ShouldRender {
         self.dot = props.dot;
+        self.selected = props.selected;
+        self.onevent = props.onevent;
+        *self.graph.borrow_mut() = props.graph;
         true
     }
+
+    fn destroy(&mut self) {
+        js! { @(no_return)
+            var handlers = @{&self.handlers};
+            handlers.click.drop();
+            handlers.connect.drop();
+            handlers.disconnect.drop();
+            handlers.check.drop();
+        }
+    }
 }
 
 impl Renderable<DotCanvas> for DotCanvas {
@@ -71,16 +372,59 @@ impl Renderable<DotCanvas> for DotCanvas {
         let svg = stdweb::web::document()
             .create_element_ns("http://www.w3.org/2000/svg", "svg")
             .unwrap();
+        let selected = self.selected.map(|id| format!("n{}", id));
         js! {
             var g = graphlibDot.read(@{&self.dot});
             // Set margins
             g.graph().marginx = 20;
             g.graph().marginy = 20;
             var svg = @{&svg};
+            var handlers = @{&self.handlers};
+            var selected = @{selected};
             // Hack: only redraw the SVG once it is actually visible,
             // otherwise firefox throws a NS_FAILURE_ERROR
             setTimeout(() => {
                 d3.select(svg).call(render, g);
+                // the node under the mouse while dragging, if any
+                var target = (source) => {
+                    var event = d3.event.sourceEvent;
+                    var element = document.elementFromPoint(event.clientX, event.clientY);
+                    var node = element && element.closest("g.node");
+                    var name = node && d3.select(node).datum();
+                    return name !== source ? name : null;
+                };
+                var line = null;
+                var drag = d3.drag()
+                    .on("drag", function(name) {
+                        if (!line) {
+                            line = d3.select(this.parentNode).append("line").attr("class", "drag-edge");
+                        }
+                        var mouse = d3.mouse(this.parentNode);
+                        var other = target(name);
+                        line.attr("x1", g.node(name).x).attr("y1", g.node(name).y)
+                            .attr("x2", mouse[0]).attr("y2", mouse[1])
+                            .classed("invalid", other !== null && !handlers.check(name, other));
+                    })
+                    .on("end", function(name) {
+                        if (!line) {
+                            return;
+                        }
+                        line.remove();
+                        line = null;
+                        var other = target(name);
+                        if (other !== null && handlers.check(name, other)) {
+                            handlers.connect(name, other);
+                        }
+                    });
+                d3.select(svg).selectAll("g.node")
+                    .classed("selected", (name) => name === selected)
+                    .on("click", (name) => handlers.click(name, d3.event.shiftKey))
+                    .call(drag);
+                d3.select(svg).selectAll("g.edgePath")
+                    .on("contextmenu", (edge) => {
+                        d3.event.preventDefault();
+                        handlers.disconnect(edge.v, edge.w);
+                    });
                 // update the viewbox of svg
                 var bbox = svg.getBBox();
                 svg.setAttribute("viewBox", (bbox.x-10)+" "+(bbox.y-10)+" "+(bbox.width+20)+" "+(bbox.height+20));
@@ -13,6 +13,7 @@ use stdweb::{
 use yew::{html, html::ChangeData, Html};
 
 use crate::{
+    graph::ParentRemoval,
     lang,
     model::{BayesOMatic, Msg},
 };
@@ -98,9 +99,20 @@ impl BayesOMatic {
             <ul class="blocky vlist">
                 <li>{ lang!(self.lang, "node-values") }</li>
                 { for node.values.iter().enumerate().map(|(i,v)| {
+                    let last = node.values.len() - 1;
                     html! {
                         <li>
-                            { v } <a href="#" onclick=|_| Msg::DelValue { node: nodeid, value_id: i }>{ "×" }</a>
+                            { if i > 0 { html! {
+                                <a href="#" onclick=|_| Msg::MoveValue { node: nodeid, value_id: i, position: i - 1 }>{ "←" }</a>
+                            }} else { html! {} }}
+                            <input size=8
+                                   oninput=|evt| Msg::RenameValue { node: nodeid, value_id: i, name: evt.value }
+                                   value={ v }>
+                            </input>
+                            { if i < last { html! {
+                                <a href="#" onclick=|_| Msg::MoveValue { node: nodeid, value_id: i, position: i + 1 }>{ "→" }</a>
+                            }} else { html! {} }}
+                            <a href="#" onclick=|_| Msg::DelValue { node: nodeid, value_id: i }>{ "×" }</a>
                         </li>
                     }
                 })}
@@ -132,6 +144,47 @@ impl BayesOMatic {
         }
     }
 
+    fn make_parent_removal(&self, nodeid: usize, parent_id: usize) -> Html<Self> {
+        let node = self.dag.get(nodeid).unwrap();
+        if node.credencies.is_none() {
+            // there is no table to preserve
+            return html! {
+                <a href="#" onclick=|_| Msg::DelParent { node: nodeid, parent_id, removal: ParentRemoval::Marginalize }>{ "×" }</a>
+            };
+        }
+        let parent = self.dag.get(parent_id).unwrap();
+        html! {
+            <select onchange=|v| if let ChangeData::Select(v) = v {
+                match v.raw_value().parse() {
+                    Ok(value) => Msg::DelParent { node: nodeid, parent_id, removal: ParentRemoval::Select(value) },
+                    Err(_) if v.raw_value() == "marginalize" => Msg::DelParent { node: nodeid, parent_id, removal: ParentRemoval::Marginalize },
+                    Err(_) => Msg::Ignore,
+                }
+            } else { Msg::Ignore }>
+                <option selected=true value="">{ "×" }</option>
+                <option selected=false value="marginalize">{ lang!(self.lang, "remove-parent-marginalize") }</option>
+                { for parent.values.iter().enumerate().map(|(i, v)| {
+                    html! {
+                        <option selected=false value={ i }>
+                            { lang!(self.lang, "remove-parent-keep", parent = &parent.label[..], value = &v[..]) }
+                        </option>
+                    }
+                })}
+            </select>
+        }
+    }
+
+    fn make_parent_reversal(&self, nodeid: usize, parent_id: usize) -> Html<Self> {
+        if self.dag.check_edge_reversal(nodeid, parent_id).is_err() {
+            return html! {};
+        }
+        let parent = self.dag.get(parent_id).unwrap();
+        html! {
+            <a href="#" title={ lang!(self.lang, "reverse-parent", parent = &parent.label[..]) }
+               onclick=|_| Msg::ReverseParent { node: nodeid, parent_id }>{ "⇄" }</a>
+        }
+    }
+
     fn make_parents_edit(&self, nodeid: usize) -> Html<Self> {
         let node = self.dag.get(nodeid).unwrap();
         html! {
@@ -140,7 +193,11 @@ impl BayesOMatic {
                 { for node.parents.iter().map(|&p| {
                     let parent = self.dag.get(p).unwrap();
                     html! {
-                        <li>{ &parent.label }<a href="#" onclick=|_| Msg::DelParent { node: nodeid, parent_id: p }>{ "×" }</a></li>
+                        <li>
+                            { &parent.label }
+                            { self.make_parent_removal(nodeid, p) }
+                            { self.make_parent_reversal(nodeid, p) }
+                        </li>
                     }
                 })}
                 <li>{ self.make_parent_seletor(nodeid) }</li>
@@ -303,9 +360,35 @@ impl BayesOMatic {
         }
     }
 
+    fn make_node_delete(&self, nodeid: usize) -> Html<Self> {
+        let node = self.dag.get(nodeid).unwrap();
+        let confirmation = if node.children.is_empty() {
+            None
+        } else {
+            Some(lang!(
+                self.lang,
+                "confirm-delete-node",
+                name = &node.label[..],
+                children = node.children.len()
+            ))
+        };
+        html! {
+            <div>
+                <a href="#" onclick=|_| {
+                    let confirmed = match confirmation {
+                        Some(ref text) => js! { return confirm(@{ text }); }.try_into().unwrap_or(false),
+                        None => true,
+                    };
+                    if confirmed { Msg::DelNode { node: nodeid } } else { Msg::Ignore }
+                }>{ lang!(self.lang, "delete-node") }</a>
+            </div>
+        }
+    }
+
     pub fn make_nodeedit_tab(&self, nodeid: usize) -> Html<Self> {
         html! {
             <div id="node-editor">
+                { self.make_node_delete(nodeid) }
                 { self.make_label_edit(nodeid) }
                 { self.make_values_edit(nodeid) }
                 { self.make_parents_edit(nodeid) }
@@ -0,0 +1,81 @@
+use stdweb::{js, unstable::TryInto};
+
+/*
+ * Helpers for exchanging files with the user
+ */
+
+/// Makes the browser download `contents` as a file named `filename`
+pub fn download(filename: &str, mime_type: &str, contents: &str) {
+    js! {
+        var blob = new Blob([@{ contents }], { type: @{ mime_type } });
+        var link = document.createElement("a");
+        link.href = URL.createObjectURL(blob);
+        link.download = @{ filename };
+        document.body.appendChild(link);
+        link.click();
+        document.body.removeChild(link);
+        setTimeout(() => URL.revokeObjectURL(link.href), 1000);
+    }
+}
+
+/// Serializes the `<svg>` element matching `selector`, inlining the rules of the
+/// stylesheets of the page that apply to it, so that it renders the same on its own
+pub fn export_svg(selector: &str) -> Option<String> {
+    js! {
+        var svg = document.querySelector(@{ selector });
+        if (!svg) {
+            return null;
+        }
+        var rules = [];
+        for (var sheet of document.styleSheets) {
+            var cssRules;
+            try {
+                cssRules = sheet.cssRules;
+            } catch (e) {
+                continue;
+            }
+            for (var rule of cssRules) {
+                if (rule.selectorText && (svg.matches(rule.selectorText) || svg.querySelector(rule.selectorText))) {
+                    rules.push(rule.cssText);
+                }
+            }
+        }
+        var style = document.createElementNS("http://www.w3.org/2000/svg", "style");
+        style.textContent = "svg { font-family: " + getComputedStyle(svg).fontFamily + "; }\n" + rules.join("\n");
+        var clone = svg.cloneNode(true);
+        clone.setAttribute("xmlns", "http://www.w3.org/2000/svg");
+        clone.insertBefore(style, clone.firstChild);
+        return new XMLSerializer().serializeToString(clone);
+    }
+    .try_into()
+    .ok()
+}
+
+/// Makes the browser download the image described by the SVG document `svg` as a PNG
+/// file named `filename`
+pub fn download_png(filename: &str, svg: &str) {
+    js! {
+        var filename = @{ filename };
+        var image = new Image();
+        image.onload = () => {
+            // render at twice the size of the diagram on the screen, for sharper text
+            var canvas = document.createElement("canvas");
+            canvas.width = image.width * 2;
+            canvas.height = image.height * 2;
+            var context = canvas.getContext("2d");
+            context.fillStyle = "white";
+            context.fillRect(0, 0, canvas.width, canvas.height);
+            context.drawImage(image, 0, 0, canvas.width, canvas.height);
+            canvas.toBlob((blob) => {
+                var link = document.createElement("a");
+                link.href = URL.createObjectURL(blob);
+                link.download = filename;
+                document.body.appendChild(link);
+                link.click();
+                document.body.removeChild(link);
+                setTimeout(() => URL.revokeObjectURL(link.href), 1000);
+            });
+        };
+        image.src = "data:image/svg+xml;charset=utf-8," + encodeURIComponent(@{ svg });
+    }
+}
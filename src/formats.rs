@@ -0,0 +1,249 @@
+use ndarray::{ArrayD, Axis, IxDyn};
+
+use crate::graph::{DeserError, DAG};
+
+/*
+ * File formats networks can be imported from and exported to
+ */
+
+/// Credency given to the values having a probability of zero, as the tables
+/// cannot contain infinite values
+const ZERO_PROBABILITY_CREDENCY: f32 = -30.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Format {
+    Json,
+    Bif,
+    XmlBif,
+    HuginNet,
+}
+
+impl Format {
+    pub const ALL: &'static [Format] =
+        &[Format::Json, Format::Bif, Format::XmlBif, Format::HuginNet];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Json => "JSON",
+            Format::Bif => "BIF",
+            Format::XmlBif => "XMLBIF",
+            Format::HuginNet => "Hugin",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Bif => "bif",
+            Format::XmlBif => "xml",
+            Format::HuginNet => "net",
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::Bif | Format::HuginNet => "text/plain",
+            Format::XmlBif => "application/xml",
+        }
+    }
+
+    pub fn from_extension(extension: &str) -> Option<Format> {
+        Format::ALL
+            .iter()
+            .cloned()
+            .find(|f| f.extension().eq_ignore_ascii_case(extension))
+    }
+
+    /// Guesses the format of a file from its name
+    pub fn from_file_name(name: &str) -> Option<Format> {
+        name.rsplit('.').next().and_then(Format::from_extension)
+    }
+
+    pub fn parse(self, text: &str) -> Result<DAG, DeserError> {
+        match self {
+            Format::Json => DAG::from_json(text),
+            Format::Bif => DAG::from_bif(text),
+            Format::XmlBif => DAG::from_xmlbif(text),
+            Format::HuginNet => DAG::from_hugin_net(text),
+        }
+    }
+
+    pub fn write(self, dag: &DAG) -> String {
+        match self {
+            Format::Json => dag.to_json(),
+            Format::Bif => dag.to_bif(),
+            Format::XmlBif => dag.to_xmlbif(),
+            Format::HuginNet => dag.to_hugin_net(),
+        }
+    }
+}
+
+/// Converts a probability table into credencies
+pub fn probabilities_to_credencies(probabilities: &ArrayD<f32>) -> ArrayD<f32> {
+    probabilities.mapv(|p| {
+        if p > 0.0 {
+            p.log10().max(ZERO_PROBABILITY_CREDENCY)
+        } else {
+            ZERO_PROBABILITY_CREDENCY
+        }
+    })
+}
+
+/// Converts credencies into a probability table, normalized for each combination of
+/// parent values
+pub fn credencies_to_probabilities(credencies: &ArrayD<f32>) -> ArrayD<f32> {
+    let mut probabilities = credencies.clone();
+    for mut lane in probabilities.lanes_mut(Axis(0)) {
+        let max = lane.fold(std::f32::NEG_INFINITY, |m, &v| m.max(v));
+        lane.mapv_inplace(|v| 10f32.powf(v - max));
+        let sum = lane.sum();
+        lane.mapv_inplace(|v| v / sum);
+    }
+    probabilities
+}
+
+/// Writes a probability with a fixed number of decimals, so that the rounding noise of
+/// the computations and the near-zero probabilities of impossible values are left out
+pub fn format_probability(probability: f32) -> String {
+    let text = format!("{:.6}", probability);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// Builds a probability table indexed by child value first from `data`, which lists the
+/// probabilities of the child for each combination of parent values in turn
+pub fn table_from_columns(shape: &[usize], data: &[f32]) -> ArrayD<f32> {
+    let mut table = ArrayD::zeros(IxDyn(shape));
+    for (combination, column) in value_combinations(&shape[1..]).zip(data.chunks(shape[0].max(1))) {
+        for (i, &p) in column.iter().enumerate() {
+            let mut index = vec![i];
+            index.extend(&combination);
+            table[IxDyn(&index)] = p;
+        }
+    }
+    table
+}
+
+/// Makes `name` usable as an identifier in formats that do not quote their names,
+/// distinct from the ones already in `taken`
+pub fn make_identifier(name: &str, fallback: &str, taken: &mut Vec<String>) -> String {
+    let mut base: String = name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if base.is_empty() {
+        base = fallback.into();
+    }
+    let mut identifier = base.clone();
+    let mut suffix = 2;
+    while taken.contains(&identifier) {
+        identifier = format!("{}_{}", base, suffix);
+        suffix += 1;
+    }
+    taken.push(identifier.clone());
+    identifier
+}
+
+/// Iterates over all the combinations of the values of nodes whose numbers of values are
+/// `shape`, the last one varying fastest
+pub fn value_combinations(shape: &[usize]) -> impl Iterator<Item = Vec<usize>> {
+    let shape = shape.to_vec();
+    let count = shape.iter().product::<usize>();
+    (0..count).map(move |mut i| {
+        let mut combination = vec![0; shape.len()];
+        for (c, &n) in combination.iter_mut().zip(&shape).rev() {
+            *c = i % n;
+            i /= n;
+        }
+        combination
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// The networks bundled as examples, in JSON
+    pub const EXAMPLES: &[&str] = &[
+        include_str!("../static/examples/en/flat_earth.json"),
+        include_str!("../static/examples/en/insect_bite.json"),
+        include_str!("../static/examples/en/rain.json"),
+        include_str!("../static/examples/fr/morsure_d-insecte.json"),
+        include_str!("../static/examples/fr/pluie.json"),
+        include_str!("../static/examples/fr/terre_plate.json"),
+    ];
+
+    /// Checks that `copy` has the same nodes as `original`, matched by label, with the same
+    /// parents and probability tables, and the same value names if `value_names` is set
+    pub fn assert_same_network(original: &DAG, copy: &DAG, value_names: bool) {
+        assert_eq!(original.iter_nodes().count(), copy.iter_nodes().count());
+        let label_of = |dag: &DAG, id: usize| dag.get(id).unwrap().label.clone();
+        for (_, node) in original.iter_nodes() {
+            let (_, other) = copy
+                .iter_nodes()
+                .find(|(_, n)| n.label == node.label)
+                .unwrap_or_else(|| panic!("missing node '{}'", node.label));
+            if value_names {
+                assert_eq!(node.values, other.values);
+            } else {
+                assert_eq!(node.values.len(), other.values.len());
+            }
+            let parents = |dag: &DAG, n: &crate::graph::Node| {
+                n.parents
+                    .iter()
+                    .map(|&p| label_of(dag, p))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(parents(original, node), parents(copy, other));
+            let expected = credencies_to_probabilities(node.credencies.as_ref().unwrap());
+            let found = credencies_to_probabilities(other.credencies.as_ref().unwrap());
+            assert_eq!(expected.shape(), found.shape());
+            for (e, f) in expected.iter().zip(found.iter()) {
+                assert!((e - f).abs() < 1e-5, "'{}': {} != {}", node.label, e, f);
+            }
+        }
+    }
+
+    #[test]
+    fn credencies_round_trip() {
+        let probabilities =
+            ArrayD::from_shape_vec(ndarray::IxDyn(&[2, 2]), vec![0.25, 1.0, 0.75, 0.0]).unwrap();
+        let back = credencies_to_probabilities(&probabilities_to_credencies(&probabilities));
+        for (p, b) in probabilities.iter().zip(back.iter()) {
+            assert!((p - b).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn combinations_last_fastest() {
+        let combinations = value_combinations(&[2, 3]).collect::<Vec<_>>();
+        assert_eq!(combinations.len(), 6);
+        assert_eq!(combinations[1], vec![0, 1]);
+        assert_eq!(combinations[3], vec![1, 0]);
+    }
+
+    #[test]
+    fn fixed_precision_probabilities() {
+        assert_eq!(format_probability(0.010000002), "0.01");
+        assert_eq!(format_probability(1e-30), "0");
+        assert_eq!(format_probability(1.0), "1");
+        assert_eq!(format_probability(0.1234567), "0.123457");
+    }
+
+    #[test]
+    fn table_columns_follow_parent_values() {
+        // two columns for a node with 3 values and a parent with 2 values
+        let table = table_from_columns(&[3, 2], &[0.1, 0.2, 0.7, 0.5, 0.5, 0.0]);
+        assert_eq!(table.shape(), &[3, 2]);
+        assert_eq!(table[IxDyn(&[2, 0])], 0.7);
+        assert_eq!(table[IxDyn(&[0, 1])], 0.5);
+        assert_eq!(table[IxDyn(&[2, 1])], 0.0);
+    }
+}
@@ -1,17 +1,63 @@
+use std::io::{Read, Write};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
 use loopybayesnet::BayesNet;
-use ndarray::{ArrayD, IxDyn};
+use ndarray::{ArrayD, Axis, IxDyn};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+use crate::formats::{
+    credencies_to_probabilities, probabilities_to_credencies, value_combinations,
+};
+
+#[derive(Clone, Debug)]
 pub struct Node {
     pub parents: Vec<usize>,
     pub children: Vec<usize>,
     pub label: String,
+    /// The identifier of the node in the file it was imported from, kept so that exporting
+    /// it again does not rename it
+    pub identifier: Option<String>,
     pub description: String,
     pub values: Vec<String>,
     pub credencies: Option<ArrayD<f32>>,
     pub cred_description: Vec<String>,
     pub observation: Option<usize>,
+    /// Uncertain evidence on the value of the node: the credency of each value given it
+    pub likelihood: Option<Vec<f32>>,
+    /// Negative evidence on the value of the node: whether each value is still possible,
+    /// at least two being so and one not
+    pub allowed: Option<Vec<bool>>,
+}
+
+impl Node {
+    /// Whether each value of the node is possible given the certain evidence on it
+    pub fn allowed_values(&self) -> Vec<bool> {
+        match (self.observation, &self.allowed) {
+            (Some(obs), _) => (0..self.values.len()).map(|v| v == obs).collect(),
+            (None, &Some(ref allowed)) => allowed.clone(),
+            (None, &None) => vec![true; self.values.len()],
+        }
+    }
+
+    /// The natural logarithm of the weight the uncertain or negative evidence on the node
+    /// gives to each of its values, the largest weight being 1
+    pub fn finding_log_weights(&self) -> Option<Vec<f32>> {
+        if let Some(ref likelihood) = self.likelihood {
+            let max = likelihood
+                .iter()
+                .fold(std::f32::NEG_INFINITY, |m, &l| m.max(l));
+            Some(likelihood.iter().map(|&l| (l - max) * 10f32.ln()).collect())
+        } else if let Some(ref allowed) = self.allowed {
+            Some(
+                allowed
+                    .iter()
+                    .map(|&a| if a { 0.0 } else { std::f32::NEG_INFINITY })
+                    .collect(),
+            )
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -21,14 +67,94 @@ pub enum EdgeError {
     AlreadyExisting,
 }
 
-#[derive(Debug)]
+/// How the credencies of a node are kept when one of its parents is removed
+#[derive(Copy, Clone, Debug)]
+pub enum ParentRemoval {
+    /// Marginalize the credencies over the removed parent, weighting its values by their
+    /// probabilities when nothing is observed
+    Marginalize,
+    /// Keep the credencies given a value of the removed parent
+    Select(usize),
+}
+
+/// What is known about the value of a node
+#[derive(Clone, Debug, PartialEq)]
+pub enum Evidence {
+    /// The node has this value
+    Value(usize),
+    /// The credency of each value of the node given uncertain evidence
+    Likelihood(Vec<f32>),
+    /// Whether each value of the node is still possible
+    Allowed(Vec<bool>),
+}
+
+impl Evidence {
+    /// Follows the values of the node after they changed, `old_values` being as in
+    /// `DAG::remap_values`
+    fn remap(&self, old_values: &[Option<usize>]) -> Option<Evidence> {
+        match *self {
+            Evidence::Value(value) => old_values
+                .iter()
+                .position(|&v| v == Some(value))
+                .map(Evidence::Value),
+            Evidence::Likelihood(ref likelihood) => Some(Evidence::Likelihood(remap_likelihood(
+                likelihood, old_values,
+            ))),
+            Evidence::Allowed(ref allowed) => allowed_evidence(remap_allowed(allowed, old_values)),
+        }
+    }
+}
+
+/// Rebuilds a likelihood vector after the values of its node changed, new values being
+/// neutral
+fn remap_likelihood(likelihood: &[f32], old_values: &[Option<usize>]) -> Vec<f32> {
+    old_values
+        .iter()
+        .map(|v| v.and_then(|v| likelihood.get(v).cloned()).unwrap_or(0.0))
+        .collect()
+}
+
+/// Rebuilds a vector of allowed values after the values of its node changed, new values
+/// being allowed as they were not ruled out
+fn remap_allowed(allowed: &[bool], old_values: &[Option<usize>]) -> Vec<bool> {
+    old_values
+        .iter()
+        .map(|v| v.and_then(|v| allowed.get(v).cloned()).unwrap_or(true))
+        .collect()
+}
+
+/// The evidence that only the `allowed` values are possible: a single value when only
+/// one is, and nothing when all or none of them are
+fn allowed_evidence(allowed: Vec<bool>) -> Option<Evidence> {
+    let count = allowed.iter().filter(|&&a| a).count();
+    if count == 0 || count == allowed.len() {
+        None
+    } else if count == 1 {
+        allowed.iter().position(|&a| a).map(Evidence::Value)
+    } else {
+        Some(Evidence::Allowed(allowed))
+    }
+}
+
+/// A named set of observations, kept aside from the ones made on the nodes
+#[derive(Clone, Debug)]
+pub struct Scenario {
+    pub name: String,
+    /// Pairs of node id and evidence, sorted by node id
+    pub observations: Vec<(usize, Evidence)>,
+}
+
+#[derive(Clone, Debug)]
 pub struct DAG {
     nodes: Vec<Option<Node>>,
+    scenarios: Vec<Scenario>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct JsonNode {
     label: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    identifier: Option<String>,
     #[serde(default)]
     description: String,
     values: Vec<String>,
@@ -37,17 +163,151 @@ pub struct JsonNode {
     credencies: Option<Vec<f32>>,
     #[serde(default)]
     cred_description: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    likelihood: Option<Vec<f32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    allowed: Option<Vec<bool>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum JsonEvidence {
+    Value(usize),
+    Likelihood(Vec<f32>),
+    Allowed(Vec<bool>),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct JsonScenario {
+    name: String,
+    /// Pairs of node index and evidence
+    observations: Vec<(usize, JsonEvidence)>,
+}
+
+/// The JSON format of networks having scenarios, the others being stored as their list
+/// of nodes only
+#[derive(Serialize, Deserialize)]
+struct JsonNetwork {
+    nodes: Vec<JsonNode>,
+    #[serde(default)]
+    scenarios: Vec<JsonScenario>,
 }
 
 #[derive(Debug)]
 pub enum DeserError {
     Json(serde_json::Error),
     Graph(EdgeError),
+    Link,
+    /// The input is not valid in the chosen file format
+    Syntax {
+        line: usize,
+        message: String,
+    },
+    /// The input is well-formed but does not describe a network we can handle
+    Invalid(String),
+    /// The input uses features of its file format that we do not support
+    Unsupported {
+        line: usize,
+        construct: String,
+    },
+}
+
+/// Rebuilds `array` along `axis`: the `i`-th slice of the result is the `old_indices[i]`-th
+/// slice of the input, or is filled with zeros if `None`
+fn remap_axis(array: &ArrayD<f32>, axis: usize, old_indices: &[Option<usize>]) -> ArrayD<f32> {
+    let mut shape = array.shape().to_vec();
+    shape[axis] = old_indices.len();
+    let mut result = ArrayD::zeros(IxDyn(&shape));
+    for (i, old) in old_indices.iter().enumerate() {
+        if let Some(old) = *old {
+            result
+                .index_axis_mut(Axis(axis), i)
+                .assign(&array.index_axis(Axis(axis), old));
+        }
+    }
+    result
+}
+
+/// Rebuilds the row descriptions of a credency table after the shape of its parents changed
+///
+/// Rows are ordered like the parent values combinations, the last parent varying the fastest.
+/// `old_rows` maps the parent values of a new row to the parent values of the old rows it comes
+/// from, whose distinct descriptions are merged. New rows without a counterpart get an empty
+/// description.
+fn remap_rows<F>(
+    rows: &[String],
+    old_shape: &[usize],
+    new_shape: &[usize],
+    old_rows: F,
+) -> Vec<String>
+where
+    F: Fn(&[usize]) -> Vec<Vec<usize>>,
+{
+    if rows.is_empty() {
+        return Vec::new();
+    }
+    let count = new_shape.iter().product::<usize>();
+    let mut result = Vec::with_capacity(count);
+    let mut row = vec![0; new_shape.len()];
+    for _ in 0..count {
+        let mut descriptions: Vec<&str> = Vec::new();
+        for old in old_rows(&row) {
+            let i = old
+                .iter()
+                .zip(old_shape)
+                .fold(0, |acc, (&v, &n)| acc * n + v);
+            if let Some(description) = rows.get(i) {
+                if !description.is_empty() && !descriptions.contains(&&description[..]) {
+                    descriptions.push(description);
+                }
+            }
+        }
+        result.push(descriptions.join("\n"));
+        // move to the next combination of parent values
+        for axis in (0..row.len()).rev() {
+            row[axis] += 1;
+            if row[axis] < new_shape[axis] {
+                break;
+            }
+            row[axis] = 0;
+        }
+    }
+    result
+}
+
+/// Marginalizes the distributions described by a credency table over one of its parent
+/// axes, given the probabilities of the values of this parent
+fn marginalize_axis(array: &ArrayD<f32>, axis: usize, weights: &[f32]) -> ArrayD<f32> {
+    // normalize the distribution of the node for each combination of parent values
+    let mut probas = array.clone();
+    for mut lane in probas.lanes_mut(Axis(0)) {
+        let max = lane.fold(std::f32::NEG_INFINITY, |m, &v| f32::max(m, v));
+        if max.is_finite() {
+            lane.mapv_inplace(|v| 10f32.powf(v - max));
+            let sum = lane.sum();
+            lane.mapv_inplace(|v| v / sum);
+        } else {
+            lane.fill(1.0);
+        }
+    }
+    let total = weights.iter().sum::<f32>();
+    if probas.shape()[axis] == 0 || total <= 0.0 {
+        return probas.sum_axis(Axis(axis));
+    }
+    let mut sum = probas.sum_axis(Axis(axis));
+    sum.fill(0.0);
+    for (slice, &weight) in probas.axis_iter(Axis(axis)).zip(weights) {
+        sum.scaled_add(weight / total, &slice);
+    }
+    sum.mapv(f32::log10)
 }
 
 impl DAG {
     pub fn new() -> DAG {
-        DAG { nodes: Vec::new() }
+        DAG {
+            nodes: Vec::new(),
+            scenarios: Vec::new(),
+        }
     }
 
     pub fn insert_node(&mut self) -> usize {
@@ -56,10 +316,13 @@ impl DAG {
             children: Vec::new(),
             description: String::new(),
             label: String::new(),
+            identifier: None,
             values: Vec::new(),
             credencies: None,
             cred_description: Vec::new(),
             observation: None,
+            likelihood: None,
+            allowed: None,
         };
         if let Some(id) = self.nodes.iter().position(|n| n.is_none()) {
             self.nodes[id] = Some(new_node);
@@ -70,6 +333,39 @@ impl DAG {
         }
     }
 
+    pub fn remove_node(&mut self, node: usize) {
+        let (parents, children) = match self.nodes.get(node) {
+            Some(&Some(ref node)) => (node.parents.clone(), node.children.clone()),
+            _ => return,
+        };
+        // detach the node from the rest of the graph first, so that the
+        // tables of its children are updated accordingly
+        for child in children {
+            self.remove_edge(child, node, ParentRemoval::Marginalize);
+        }
+        for parent in parents {
+            self.remove_edge(node, parent, ParentRemoval::Marginalize);
+        }
+        for scenario in &mut self.scenarios {
+            scenario.observations.retain(|&(id, _)| id != node);
+        }
+        self.nodes[node] = None;
+    }
+
+    /// The probability table of `node`, indexed by its value then the values of its
+    /// parents, uniform if its credencies are not set
+    pub fn probability_table(&self, node: usize) -> Option<ArrayD<f32>> {
+        let node_ref = self.get(node)?;
+        let mut shape = vec![node_ref.values.len()];
+        shape.extend(self.parent_shape(node));
+        Some(credencies_to_probabilities(
+            &node_ref
+                .credencies
+                .clone()
+                .unwrap_or_else(|| ArrayD::zeros(IxDyn(&shape))),
+        ))
+    }
+
     pub fn check_edge_addition(&self, child: usize, parent: usize) -> Result<(), EdgeError> {
         if let Some(&Some(ref node)) = self.nodes.get(parent) {
             if parent == child {
@@ -78,23 +374,9 @@ impl DAG {
             if node.children.contains(&child) {
                 return Err(EdgeError::AlreadyExisting);
             }
-            let mut ancestors = node.parents.clone();
-            let mut visited = vec![parent];
-            // iteratively check all ancestors for equality with the child, if we find
-            // any adding this edge would create a cycle
-            loop {
-                let id = match ancestors.pop() {
-                    Some(v) => v,
-                    None => break,
-                };
-                if id == child {
-                    return Err(EdgeError::WouldCycle);
-                }
-                if visited.contains(&id) {
-                    continue;
-                }
-                visited.push(id);
-                ancestors.extend(&self.nodes[id].as_ref().unwrap().parents);
+            // if the child is an ancestor of the parent, adding this edge would create a cycle
+            if self.has_ancestor(node.parents.clone(), child) {
+                return Err(EdgeError::WouldCycle);
             }
         } else {
             return Err(EdgeError::BadNode);
@@ -102,6 +384,37 @@ impl DAG {
         Ok(())
     }
 
+    /// Whether `ancestor` is among `nodes` or their ancestors
+    fn has_ancestor(&self, mut nodes: Vec<usize>, ancestor: usize) -> bool {
+        let mut visited = Vec::new();
+        // iteratively check all ancestors for equality with the searched one
+        while let Some(id) = nodes.pop() {
+            if id == ancestor {
+                return true;
+            }
+            if visited.contains(&id) {
+                continue;
+            }
+            visited.push(id);
+            nodes.extend(&self.nodes[id].as_ref().unwrap().parents);
+        }
+        false
+    }
+
+    /// Checks that the edge from `parent` to `child` exists and can be reversed
+    pub fn check_edge_reversal(&self, child: usize, parent: usize) -> Result<(), EdgeError> {
+        let node = match self.nodes.get(child) {
+            Some(&Some(ref node)) if node.parents.contains(&parent) => node,
+            _ => return Err(EdgeError::BadNode),
+        };
+        // another path from the parent to the child would become a cycle
+        let others = node.parents.iter().cloned().filter(|&p| p != parent);
+        if self.has_ancestor(others.collect(), parent) {
+            return Err(EdgeError::WouldCycle);
+        }
+        Ok(())
+    }
+
     fn count_parent_values(&self, node: usize) -> usize {
         if let Some(&Some(ref node)) = self.nodes.get(node) {
             let mut values = 1;
@@ -114,6 +427,16 @@ impl DAG {
         }
     }
 
+    fn parent_shape(&self, node: usize) -> Vec<usize> {
+        self.nodes[node]
+            .as_ref()
+            .unwrap()
+            .parents
+            .iter()
+            .map(|&p| self.nodes[p].as_ref().unwrap().values.len())
+            .collect()
+    }
+
     pub fn add_edge(&mut self, child: usize, parent: usize) -> Result<(), EdgeError> {
         // check if a cycle would be created...
         self.check_edge_addition(child, parent)?;
@@ -121,9 +444,6 @@ impl DAG {
         // no cycle, all is good, insert
         if let Some(&mut Some(ref mut node)) = self.nodes.get_mut(child) {
             node.parents.push(parent);
-            // reset the credencies when changing the parents
-            node.credencies = None;
-            node.cred_description = Vec::new();
         } else {
             return Err(EdgeError::BadNode);
         }
@@ -132,47 +452,381 @@ impl DAG {
         if !node.children.contains(&child) {
             node.children.push(child);
         }
+
+        // the existing credencies are independent of the new parent: broadcast them
+        // along a new axis
+        let new_shape = self.parent_shape(child);
+        let old_shape = &new_shape[..new_shape.len() - 1];
+        let node = self.nodes[child].as_mut().unwrap();
+        node.credencies = node.credencies.take().map(|array| {
+            let mut shape = array.shape().to_vec();
+            shape.push(new_shape[new_shape.len() - 1]);
+            let array = array.insert_axis(Axis(shape.len() - 1));
+            array.broadcast(IxDyn(&shape)).unwrap().to_owned()
+        });
+        node.cred_description = remap_rows(&node.cred_description, old_shape, &new_shape, |row| {
+            vec![row[..row.len() - 1].to_vec()]
+        });
         Ok(())
     }
 
-    pub fn remove_edge(&mut self, child: usize, parent: usize) {
-        if let Some(&mut Some(ref mut node)) = self.nodes.get_mut(child) {
-            node.parents.retain(|&v| v != parent);
-            // reset the credencies when changing the parents
-            node.credencies = None;
-            node.cred_description = Vec::new();
-        }
+    pub fn remove_edge(&mut self, child: usize, parent: usize, removal: ParentRemoval) {
+        let old_shape = match self.nodes.get(child) {
+            Some(&Some(ref node)) if node.parents.contains(&parent) => self.parent_shape(child),
+            _ => return,
+        };
+        let axis = self.nodes[child]
+            .as_ref()
+            .unwrap()
+            .parents
+            .iter()
+            .position(|&p| p == parent)
+            .unwrap();
+        let count = old_shape[axis];
+        // only keep the value of the parent that was asked, if it exists
+        let removal = match removal {
+            ParentRemoval::Select(value) if value < count => ParentRemoval::Select(value),
+            _ => ParentRemoval::Marginalize,
+        };
+        let has_credencies = self.nodes[child].as_ref().unwrap().credencies.is_some();
+        let weights = match removal {
+            ParentRemoval::Marginalize if has_credencies => self
+                .prior_probabilities(parent)
+                .unwrap_or_else(|| vec![1.0; count]),
+            _ => Vec::new(),
+        };
+        let node = self.nodes[child].as_mut().unwrap();
+        node.parents.remove(axis);
+        node.credencies = node.credencies.take().map(|array| match removal {
+            ParentRemoval::Marginalize => marginalize_axis(&array, axis + 1, &weights),
+            ParentRemoval::Select(value) => array.index_axis(Axis(axis + 1), value).to_owned(),
+        });
+        let mut new_shape = old_shape.clone();
+        new_shape.remove(axis);
+        node.cred_description = remap_rows(&node.cred_description, &old_shape, &new_shape, |row| {
+            let values = match removal {
+                ParentRemoval::Marginalize => (0..count).collect(),
+                ParentRemoval::Select(value) => vec![value],
+            };
+            values
+                .into_iter()
+                .map(|value| {
+                    let mut old_row = row.to_vec();
+                    old_row.insert(axis, value);
+                    old_row
+                })
+                .collect()
+        });
+
         if let Some(&mut Some(ref mut node)) = self.nodes.get_mut(parent) {
             node.children.retain(|&v| v != child);
         }
     }
 
-    pub fn add_value(&mut self, node: usize, value: String) {
-        let children = if let Some(&mut Some(ref mut node)) = self.nodes.get_mut(node) {
-            node.values.push(value);
-            // reset the credencies when changing the values
-            node.credencies = None;
-            node.cred_description = Vec::new();
-            node.observation = None;
-            node.children.clone()
+    /// The probabilities of the values of `node` when nothing is observed, or `None` if
+    /// one of its ancestors has no values
+    ///
+    /// Only the ancestors of the node are involved. The probabilities are exact, unless
+    /// there are too many ancestors and loopy belief propagation is used instead.
+    fn prior_probabilities(&self, node: usize) -> Option<Vec<f32>> {
+        let mut ancestors = Vec::new();
+        let mut stack = vec![node];
+        while let Some(id) = stack.pop() {
+            if !ancestors.contains(&id) {
+                ancestors.push(id);
+                stack.extend(&self.nodes[id].as_ref().unwrap().parents);
+            }
+        }
+        let mut dag = DAG::new();
+        dag.nodes = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(id, node)| match node {
+                &Some(ref node) if ancestors.contains(&id) => {
+                    let mut node = node.clone();
+                    node.children.retain(|c| ancestors.contains(c));
+                    Some(node)
+                }
+                _ => None,
+            })
+            .collect();
+        dag.clear_observations();
+        let beliefs = if crate::inference::exact_inference_cost(&dag)
+            <= crate::inference::EXACT_INFERENCE_BUDGET
+        {
+            crate::inference::exact_beliefs(&dag).ok()?
         } else {
-            Vec::new()
+            let (mut bayesnet, mapping) = dag.make_bayesnet().ok()?;
+            for _ in 0..100 {
+                bayesnet.step();
+            }
+            bayesnet.beliefs().into_iter().zip(mapping).collect()
         };
-        // also reset the credencies of the children
-        for child in children {
-            if let Some(&mut Some(ref mut node)) = self.nodes.get_mut(child) {
-                node.credencies = None;
+        beliefs
+            .into_iter()
+            .find(|&(_, id)| id == node)
+            .map(|(beliefs, _)| beliefs.as_probabilities().to_vec())
+    }
+
+    /// Reverses the edge from `parent` to `child`, so that `child` becomes a parent of
+    /// `parent` instead
+    ///
+    /// Both nodes also get the parents of each other, and their credencies are recomputed
+    /// so that they describe the same joint distribution as before. The descriptions of
+    /// their rows are lost, as the rows do not have the same meaning anymore.
+    pub fn reverse_edge(&mut self, child: usize, parent: usize) -> Result<(), EdgeError> {
+        self.check_edge_reversal(child, parent)?;
+
+        let old_child_parents = self.nodes[child].as_ref().unwrap().parents.clone();
+        let old_parent_parents = self.nodes[parent].as_ref().unwrap().parents.clone();
+        let mut child_parents = old_child_parents
+            .iter()
+            .cloned()
+            .filter(|&p| p != parent)
+            .collect::<Vec<_>>();
+        let mut parent_parents = old_parent_parents.clone();
+        for &p in &old_parent_parents {
+            if !child_parents.contains(&p) {
+                child_parents.push(p);
+            }
+        }
+        for &p in &child_parents {
+            if !parent_parents.contains(&p) {
+                parent_parents.push(p);
+            }
+        }
+        parent_parents.push(child);
+
+        let has_credencies = self.nodes[child].as_ref().unwrap().credencies.is_some()
+            || self.nodes[parent].as_ref().unwrap().credencies.is_some();
+        let credencies = if has_credencies {
+            Some(self.reversed_credencies(child, parent, &child_parents, &parent_parents))
+        } else {
+            None
+        };
+
+        // rewire the graph
+        for &p in &old_child_parents {
+            let node = self.nodes[p].as_mut().unwrap();
+            node.children.retain(|&c| c != child);
+        }
+        for &p in &child_parents {
+            self.nodes[p].as_mut().unwrap().children.push(child);
+        }
+        for &p in &parent_parents {
+            let node = self.nodes[p].as_mut().unwrap();
+            if !node.children.contains(&parent) {
+                node.children.push(parent);
             }
         }
+        let (parent_credencies, child_credencies) = match credencies {
+            Some((p, c)) => (Some(p), Some(c)),
+            None => (None, None),
+        };
+        let node = self.nodes[child].as_mut().unwrap();
+        node.parents = child_parents;
+        node.credencies = child_credencies;
+        node.cred_description.clear();
+        let node = self.nodes[parent].as_mut().unwrap();
+        node.parents = parent_parents;
+        node.credencies = parent_credencies;
+        node.cred_description.clear();
+        Ok(())
+    }
+
+    /// Computes the credencies of `parent` and `child` once the edge between them is
+    /// reversed and they have their new parents, as P(parent | child, others) and
+    /// P(child | others) from P(parent | its parents) P(child | parent, its other parents)
+    fn reversed_credencies(
+        &self,
+        child: usize,
+        parent: usize,
+        child_parents: &[usize],
+        parent_parents: &[usize],
+    ) -> (ArrayD<f32>, ArrayD<f32>) {
+        let value_count = |id: usize| self.nodes[id].as_ref().unwrap().values.len();
+        let old_child = self.probability_table(child).unwrap();
+        let old_parent = self.probability_table(parent).unwrap();
+        let old_child_parents = &self.nodes[child].as_ref().unwrap().parents;
+        let old_parent_parents = &self.nodes[parent].as_ref().unwrap().parents;
+        let (child_count, parent_count) = (value_count(child), value_count(parent));
+
+        let mut child_shape = vec![child_count];
+        child_shape.extend(child_parents.iter().map(|&p| value_count(p)));
+        let mut parent_shape = vec![parent_count];
+        parent_shape.extend(parent_parents.iter().map(|&p| value_count(p)));
+        let mut new_child = ArrayD::zeros(IxDyn(&child_shape));
+        let mut new_parent = ArrayD::zeros(IxDyn(&parent_shape));
+
+        // the new parents of the child are all the other parents of both nodes
+        for combination in value_combinations(&child_shape[1..]) {
+            let value =
+                |id: usize| combination[child_parents.iter().position(|&p| p == id).unwrap()];
+            for c in 0..child_count {
+                let joint = (0..parent_count)
+                    .map(|v| {
+                        let mut parent_index = vec![v];
+                        parent_index.extend(old_parent_parents.iter().map(|&p| value(p)));
+                        let mut child_index = vec![c];
+                        child_index.extend(old_child_parents.iter().map(|&p| {
+                            if p == parent {
+                                v
+                            } else {
+                                value(p)
+                            }
+                        }));
+                        old_parent[IxDyn(&parent_index)] * old_child[IxDyn(&child_index)]
+                    })
+                    .collect::<Vec<f32>>();
+                let marginal = joint.iter().sum::<f32>();
+                let mut index = vec![c];
+                index.extend(&combination);
+                new_child[IxDyn(&index)] = marginal;
+                for (v, &p) in joint.iter().enumerate() {
+                    let mut index = vec![v];
+                    index.extend(
+                        parent_parents[..parent_parents.len() - 1]
+                            .iter()
+                            .map(|&p| value(p)),
+                    );
+                    index.push(c);
+                    new_parent[IxDyn(&index)] = if marginal > 0.0 {
+                        p / marginal
+                    } else {
+                        1.0 / parent_count as f32
+                    };
+                }
+            }
+        }
+        (
+            probabilities_to_credencies(&new_parent),
+            probabilities_to_credencies(&new_child),
+        )
+    }
+
+    pub fn add_value(&mut self, node: usize, value: String) {
+        let old_count = if let Some(&mut Some(ref mut node)) = self.nodes.get_mut(node) {
+            node.values.push(value);
+            node.values.len() - 1
+        } else {
+            return;
+        };
+        // existing values are kept, the new one is appended
+        let old_values = (0..old_count)
+            .map(Some)
+            .chain(Some(None))
+            .collect::<Vec<_>>();
+        self.remap_values(node, old_count, &old_values);
     }
 
     pub fn remove_value(&mut self, node: usize, value_id: usize) {
-        if let Some(&mut Some(ref mut node)) = self.nodes.get_mut(node) {
+        let old_count = if let Some(&mut Some(ref mut node)) = self.nodes.get_mut(node) {
+            if value_id >= node.values.len() {
+                return;
+            }
             node.values.remove(value_id);
-            // reset the credencies when changing the values
-            node.credencies = None;
-            node.cred_description = Vec::new();
-            node.observation = None;
+            node.observation = match node.observation {
+                Some(obs) if obs == value_id => None,
+                Some(obs) if obs > value_id => Some(obs - 1),
+                obs => obs,
+            };
+            node.values.len() + 1
+        } else {
+            return;
+        };
+        let old_values = (0..old_count)
+            .filter(|&i| i != value_id)
+            .map(Some)
+            .collect::<Vec<_>>();
+        self.remap_values(node, old_count, &old_values);
+    }
+
+    pub fn rename_value(&mut self, node: usize, value_id: usize, name: String) {
+        if let Some(&mut Some(ref mut node)) = self.nodes.get_mut(node) {
+            if let Some(value) = node.values.get_mut(value_id) {
+                *value = name;
+            }
+        }
+    }
+
+    pub fn move_value(&mut self, node: usize, value_id: usize, position: usize) {
+        let old_values = if let Some(&mut Some(ref mut node)) = self.nodes.get_mut(node) {
+            if value_id >= node.values.len() || position >= node.values.len() {
+                return;
+            }
+            let value = node.values.remove(value_id);
+            node.values.insert(position, value);
+            let mut old_values = (0..node.values.len()).map(Some).collect::<Vec<_>>();
+            let moved = old_values.remove(value_id);
+            old_values.insert(position, moved);
+            node.observation = node
+                .observation
+                .and_then(|obs| old_values.iter().position(|&v| v == Some(obs)));
+            old_values
+        } else {
+            return;
+        };
+        self.remap_values(node, old_values.len(), &old_values);
+    }
+
+    /// Updates the tables of a node and of its children after its list of values changed
+    ///
+    /// `old_values[i]` is the index the `i`-th value of the node had before the change (out
+    /// of `old_count` values), or `None` for a new value, whose credencies are then neutral.
+    fn remap_values(&mut self, node: usize, old_count: usize, old_values: &[Option<usize>]) {
+        for scenario in &mut self.scenarios {
+            scenario.observations = scenario
+                .observations
+                .iter()
+                .filter_map(|&(id, ref evidence)| {
+                    if id != node {
+                        return Some((id, evidence.clone()));
+                    }
+                    evidence.remap(old_values).map(|evidence| (id, evidence))
+                })
+                .collect();
+        }
+        let children = {
+            let node = self.nodes[node].as_mut().unwrap();
+            node.likelihood = node
+                .likelihood
+                .take()
+                .map(|likelihood| remap_likelihood(&likelihood, old_values));
+            if let Some(allowed) = node.allowed.take() {
+                match allowed_evidence(remap_allowed(&allowed, old_values)) {
+                    Some(Evidence::Value(value)) => node.observation = Some(value),
+                    Some(Evidence::Allowed(allowed)) => node.allowed = Some(allowed),
+                    _ => {}
+                }
+            }
+            node.credencies = node
+                .credencies
+                .take()
+                .map(|array| remap_axis(&array, 0, old_values));
+            node.children.clone()
+        };
+        for child in children {
+            let new_shape = self.parent_shape(child);
+            let child = self.nodes[child].as_mut().unwrap();
+            let axis = child.parents.iter().position(|&p| p == node).unwrap();
+            child.credencies = child
+                .credencies
+                .take()
+                .map(|array| remap_axis(&array, axis + 1, old_values));
+            let mut old_shape = new_shape.clone();
+            old_shape[axis] = old_count;
+            child.cred_description =
+                remap_rows(&child.cred_description, &old_shape, &new_shape, |row| {
+                    old_values[row[axis]]
+                        .map(|value| {
+                            let mut old_row = row.to_vec();
+                            old_row[axis] = value;
+                            old_row
+                        })
+                        .into_iter()
+                        .collect()
+                });
         }
     }
 
@@ -182,6 +836,12 @@ impl DAG {
         }
     }
 
+    pub fn set_identifier(&mut self, node: usize, identifier: Option<String>) {
+        if let Some(&mut Some(ref mut node)) = self.nodes.get_mut(node) {
+            node.identifier = identifier;
+        }
+    }
+
     pub fn set_credencies(&mut self, node: usize, credencies: ArrayD<f32>) -> Result<(), ()> {
         // sanity check, the dimensions of the array must match
         if let Some(&Some(ref node)) = self.nodes.get(node) {
@@ -201,9 +861,179 @@ impl DAG {
         Ok(())
     }
 
+    /// Sets the observed value of a node, replacing any uncertain evidence on it
     pub fn set_observation(&mut self, node: usize, observation: Option<usize>) {
         if let Some(&mut Some(ref mut node)) = self.nodes.get_mut(node) {
             node.observation = observation;
+            node.likelihood = None;
+            node.allowed = None;
+        }
+    }
+
+    /// Sets uncertain evidence on a node, replacing its observed value if any
+    ///
+    /// The likelihood must have one credency per value of the node.
+    pub fn set_likelihood(&mut self, node: usize, likelihood: Option<Vec<f32>>) -> Result<(), ()> {
+        if let Some(&mut Some(ref mut node)) = self.nodes.get_mut(node) {
+            match likelihood {
+                Some(ref l) if l.len() != node.values.len() => return Err(()),
+                Some(_) => {
+                    node.observation = None;
+                    node.allowed = None;
+                }
+                None => {}
+            }
+            node.likelihood = likelihood;
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+
+    /// Rules out the values of a node that are not allowed, replacing any other evidence
+    /// on it
+    ///
+    /// There must be one flag per value of the node, at least one of them set. Allowing a
+    /// single value observes it, and allowing all of them removes the evidence.
+    pub fn set_allowed(&mut self, node: usize, allowed: Vec<bool>) -> Result<(), ()> {
+        if let Some(&mut Some(ref mut node)) = self.nodes.get_mut(node) {
+            if allowed.len() != node.values.len() || !allowed.contains(&true) {
+                return Err(());
+            }
+            node.observation = None;
+            node.likelihood = None;
+            node.allowed = None;
+            match allowed_evidence(allowed) {
+                Some(Evidence::Value(value)) => node.observation = Some(value),
+                Some(Evidence::Allowed(allowed)) => node.allowed = Some(allowed),
+                _ => {}
+            }
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+
+    pub fn clear_observations(&mut self) {
+        for node in self.nodes.iter_mut().flatten() {
+            node.observation = None;
+            node.likelihood = None;
+            node.allowed = None;
+        }
+    }
+
+    /// Makes the same observations as `other` on the nodes having the same labels, failing
+    /// if an observed node or value of `other` has no counterpart here
+    pub fn copy_observations(&mut self, other: &DAG) -> Result<(), ()> {
+        self.clear_observations();
+        for (id, evidence) in other.observations() {
+            let observed = other.get(id).unwrap();
+            let node = self
+                .nodes
+                .iter_mut()
+                .flatten()
+                .find(|n| n.label == observed.label)
+                .ok_or(())?;
+            // the index of each value of the other node among the values of this one
+            let values = observed
+                .values
+                .iter()
+                .map(|value| node.values.iter().position(|v| v == value))
+                .collect::<Vec<_>>();
+            match evidence {
+                Evidence::Value(value) => {
+                    node.observation = Some(values.get(value).and_then(|&v| v).ok_or(())?);
+                }
+                Evidence::Likelihood(likelihood) => {
+                    // the values missing from the other node get no credency from the evidence
+                    let mut own = vec![0.0; node.values.len()];
+                    for (value, credency) in values.into_iter().zip(likelihood) {
+                        own[value.ok_or(())?] = credency;
+                    }
+                    node.likelihood = Some(own);
+                }
+                Evidence::Allowed(allowed) => {
+                    // the values missing from the other node were not ruled out
+                    let mut own = vec![true; node.values.len()];
+                    for (value, allowed) in values.into_iter().zip(allowed) {
+                        own[value.ok_or(())?] = allowed;
+                    }
+                    match allowed_evidence(own) {
+                        Some(Evidence::Value(value)) => node.observation = Some(value),
+                        Some(Evidence::Allowed(allowed)) => node.allowed = Some(allowed),
+                        _ => {}
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The evidence currently known, as pairs of node id and evidence
+    pub fn observations(&self) -> Vec<(usize, Evidence)> {
+        self.iter_nodes()
+            .filter_map(|(id, node)| {
+                let evidence = if let Some(value) = node.observation {
+                    Evidence::Value(value)
+                } else if let Some(ref likelihood) = node.likelihood {
+                    Evidence::Likelihood(likelihood.clone())
+                } else if let Some(ref allowed) = node.allowed {
+                    Evidence::Allowed(allowed.clone())
+                } else {
+                    return None;
+                };
+                Some((id, evidence))
+            })
+            .collect()
+    }
+
+    pub fn scenarios(&self) -> &[Scenario] {
+        &self.scenarios
+    }
+
+    /// Saves the current observations as a new scenario
+    pub fn add_scenario(&mut self, name: String) {
+        let observations = self.observations();
+        self.scenarios.push(Scenario { name, observations });
+    }
+
+    /// Replaces the observations of a scenario by the current ones
+    pub fn update_scenario(&mut self, scenario: usize) {
+        let observations = self.observations();
+        if let Some(scenario) = self.scenarios.get_mut(scenario) {
+            scenario.observations = observations;
+        }
+    }
+
+    pub fn rename_scenario(&mut self, scenario: usize, name: String) {
+        if let Some(scenario) = self.scenarios.get_mut(scenario) {
+            scenario.name = name;
+        }
+    }
+
+    pub fn remove_scenario(&mut self, scenario: usize) {
+        if scenario < self.scenarios.len() {
+            self.scenarios.remove(scenario);
+        }
+    }
+
+    /// Makes the observations of a scenario on the nodes
+    pub fn apply_scenario(&mut self, scenario: usize) {
+        let observations = match self.scenarios.get(scenario) {
+            Some(scenario) => scenario.observations.clone(),
+            None => return,
+        };
+        self.clear_observations();
+        for (id, evidence) in observations {
+            match evidence {
+                Evidence::Value(value) => self.set_observation(id, Some(value)),
+                Evidence::Likelihood(likelihood) => {
+                    let _ = self.set_likelihood(id, Some(likelihood));
+                }
+                Evidence::Allowed(allowed) => {
+                    let _ = self.set_allowed(id, allowed);
+                }
+            }
         }
     }
 
@@ -234,6 +1064,11 @@ impl DAG {
         self.nodes.get(id).and_then(|o| o.as_ref())
     }
 
+    /// The ids of the nodes of the graph, parents always coming before their children
+    pub fn topological_order(&self) -> Vec<usize> {
+        self.compact_ids().0
+    }
+
     fn compact_ids(&self) -> (Vec<usize>, Vec<Option<usize>>) {
         // Order the nodes of the graph into a topological order for insertion into
         // loopybayesnet
@@ -296,6 +1131,21 @@ impl DAG {
                 observation.push((loopy_id, ev));
             }
         }
+        // uncertain or negative evidence is an observed child with two values, added after
+        // the real nodes so that their ids are unchanged, whose first value has a
+        // probability proportional to the weight the evidence gives to each value
+        for (i, &n) in order.iter().enumerate() {
+            let weights = match self.nodes[n].as_ref().unwrap().finding_log_weights() {
+                Some(weights) => weights,
+                None => continue,
+            };
+            let mut log_probas = weights.clone();
+            log_probas.extend(weights.iter().map(|&w| (1.0 - w.exp()).ln()));
+            let log_probas =
+                ArrayD::from_shape_vec(IxDyn(&[2, weights.len()]), log_probas).map_err(|_| ())?;
+            let loopy_id = net.add_node_from_log_probabilities(&[i], log_probas);
+            observation.push((loopy_id, 0));
+        }
 
         net.set_evidence(&observation);
 
@@ -309,7 +1159,7 @@ impl DAG {
         })
     }
 
-    pub fn to_json(&self) -> String {
+    fn json_network(&self) -> JsonNetwork {
         let (order, map) = self.compact_ids();
         let mut nodelist: Vec<JsonNode> = Vec::with_capacity(order.len());
 
@@ -317,6 +1167,7 @@ impl DAG {
             let node = self.nodes[n].as_ref().unwrap();
             nodelist.push(JsonNode {
                 label: node.label.clone(),
+                identifier: node.identifier.clone(),
                 values: node.values.clone(),
                 description: node.description.clone(),
                 parents: node
@@ -330,20 +1181,86 @@ impl DAG {
                     .as_ref()
                     .map(|a| a.iter().cloned().collect()),
                 cred_description: node.cred_description.clone(),
+                likelihood: node.likelihood.clone(),
+                allowed: node.allowed.clone(),
             });
         }
 
-        serde_json::to_string_pretty(&nodelist).unwrap()
+        let scenarios = self
+            .scenarios
+            .iter()
+            .map(|scenario| JsonScenario {
+                name: scenario.name.clone(),
+                observations: scenario
+                    .observations
+                    .iter()
+                    .map(|&(id, ref evidence)| {
+                        let evidence = match *evidence {
+                            Evidence::Value(value) => JsonEvidence::Value(value),
+                            Evidence::Likelihood(ref l) => JsonEvidence::Likelihood(l.clone()),
+                            Evidence::Allowed(ref a) => JsonEvidence::Allowed(a.clone()),
+                        };
+                        (map[id].unwrap(), evidence)
+                    })
+                    .collect(),
+            })
+            .collect();
+        JsonNetwork {
+            nodes: nodelist,
+            scenarios,
+        }
+    }
+
+    /// Serializes the network, as the plain list of its nodes when it has no scenarios
+    /// for compatibility
+    pub fn write_json(&self, pretty: bool) -> String {
+        let network = self.json_network();
+        let result = match (network.scenarios.is_empty(), pretty) {
+            (true, true) => serde_json::to_string_pretty(&network.nodes),
+            (true, false) => serde_json::to_string(&network.nodes),
+            (false, true) => serde_json::to_string_pretty(&network),
+            (false, false) => serde_json::to_string(&network),
+        };
+        result.unwrap()
+    }
+
+    pub fn to_json(&self) -> String {
+        self.write_json(true)
+    }
+
+    /// Encodes the graph as compressed and URL-safe text, for use in shareable links
+    pub fn to_link_fragment(&self) -> String {
+        let json = self.write_json(false);
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(json.as_bytes()).unwrap();
+        base64::encode_config(&encoder.finish().unwrap(), base64::URL_SAFE_NO_PAD)
+    }
+
+    pub fn from_link_fragment(fragment: &str) -> Result<DAG, DeserError> {
+        let compressed = base64::decode_config(fragment, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| DeserError::Link)?;
+        let mut json = String::new();
+        DeflateDecoder::new(&compressed[..])
+            .read_to_string(&mut json)
+            .map_err(|_| DeserError::Link)?;
+        DAG::from_json(&json)
     }
 
     pub fn from_json(json: &str) -> Result<DAG, DeserError> {
-        let contents: Vec<JsonNode> = serde_json::from_str(json).map_err(DeserError::Json)?;
+        let (contents, scenarios) = if json.trim_start().starts_with('{') {
+            let network: JsonNetwork = serde_json::from_str(json).map_err(DeserError::Json)?;
+            (network.nodes, network.scenarios)
+        } else {
+            let nodes: Vec<JsonNode> = serde_json::from_str(json).map_err(DeserError::Json)?;
+            (nodes, Vec::new())
+        };
 
         let mut dag = DAG::new();
 
         for node in &contents {
             let id = dag.insert_node();
             dag.set_label(id, node.label.clone());
+            dag.set_identifier(id, node.identifier.clone());
             for &p in &node.parents {
                 dag.add_edge(id, p).map_err(DeserError::Graph)?;
             }
@@ -351,6 +1268,12 @@ impl DAG {
                 dag.add_value(id, v.into());
             }
             dag.set_observation(id, node.observation);
+            // ignore bad likelihoods
+            let _ = dag.set_likelihood(id, node.likelihood.clone());
+            if let Some(ref allowed) = node.allowed {
+                // ignore bad lists of allowed values
+                let _ = dag.set_allowed(id, allowed.clone());
+            }
             dag.set_description(id, node.description.clone());
             // ingore bad descriptions
             let _ = dag.set_cred_descriptions(id, node.cred_description.clone());
@@ -368,7 +1291,324 @@ impl DAG {
                 let _ = dag.set_credencies(id, array);
             }
         }
+        for scenario in scenarios {
+            // ignore observations of unknown nodes or values
+            let mut observations = scenario
+                .observations
+                .into_iter()
+                .filter_map(|(id, evidence)| {
+                    let count = contents.get(id)?.values.len();
+                    match evidence {
+                        JsonEvidence::Value(value) if value < count => {
+                            Some((id, Evidence::Value(value)))
+                        }
+                        JsonEvidence::Likelihood(l) if l.len() == count => {
+                            Some((id, Evidence::Likelihood(l)))
+                        }
+                        JsonEvidence::Allowed(a) if a.len() == count => {
+                            allowed_evidence(a).map(|evidence| (id, evidence))
+                        }
+                        _ => None,
+                    }
+                })
+                .collect::<Vec<_>>();
+            observations.sort_by_key(|&(id, _)| id);
+            observations.dedup_by_key(|&mut (id, _)| id);
+            dag.scenarios.push(Scenario {
+                name: scenario.name,
+                observations,
+            });
+        }
 
         Ok(dag)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a network of a node `a` with values x, y and z, and of its child `b` with
+    /// values p and q
+    fn two_nodes() -> DAG {
+        let mut dag = DAG::new();
+        let a = dag.insert_node();
+        dag.set_label(a, "a".into());
+        for v in &["x", "y", "z"] {
+            dag.add_value(a, v.to_string());
+        }
+        dag.set_credencies(
+            a,
+            ArrayD::from_shape_vec(IxDyn(&[3]), vec![0.0, 1.0, 2.0]).unwrap(),
+        )
+        .unwrap();
+        let b = dag.insert_node();
+        dag.set_label(b, "b".into());
+        dag.add_value(b, "p".into());
+        dag.add_value(b, "q".into());
+        dag.add_edge(b, a).unwrap();
+        let table = vec![0.0, 0.5, 1.0, -1.0, -0.5, 0.0];
+        dag.set_credencies(b, ArrayD::from_shape_vec(IxDyn(&[2, 3]), table).unwrap())
+            .unwrap();
+        let rows = vec!["given x".into(), "given y".into(), "given z".into()];
+        dag.set_cred_descriptions(b, rows).unwrap();
+        dag
+    }
+
+    fn credencies(dag: &DAG, node: usize) -> Vec<f32> {
+        dag.get(node)
+            .unwrap()
+            .credencies
+            .as_ref()
+            .unwrap()
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn add_value_keeps_tables() {
+        let mut dag = two_nodes();
+        dag.add_value(0, "w".into());
+        assert_eq!(credencies(&dag, 0), vec![0.0, 1.0, 2.0, 0.0]);
+        assert_eq!(
+            dag.get(1).unwrap().credencies.as_ref().unwrap().shape(),
+            &[2, 4]
+        );
+        assert_eq!(
+            credencies(&dag, 1),
+            vec![0.0, 0.5, 1.0, 0.0, -1.0, -0.5, 0.0, 0.0]
+        );
+        assert_eq!(dag.get(1).unwrap().cred_description[3], "");
+        // the new value of the child is neutral
+        dag.add_value(1, "r".into());
+        assert_eq!(
+            dag.get(1).unwrap().credencies.as_ref().unwrap().shape(),
+            &[3, 4]
+        );
+        assert_eq!(&credencies(&dag, 1)[8..], &[0.0; 4]);
+    }
+
+    #[test]
+    fn remove_value_keeps_tables() {
+        let mut dag = two_nodes();
+        dag.set_observation(0, Some(2));
+        dag.remove_value(0, 1);
+        assert_eq!(credencies(&dag, 0), vec![0.0, 2.0]);
+        assert_eq!(credencies(&dag, 1), vec![0.0, 1.0, -1.0, 0.0]);
+        assert_eq!(
+            dag.get(1).unwrap().cred_description,
+            vec!["given x", "given z"]
+        );
+        assert_eq!(dag.get(0).unwrap().observation, Some(1));
+        dag.remove_value(1, 0);
+        assert_eq!(credencies(&dag, 1), vec![-1.0, 0.0]);
+    }
+
+    #[test]
+    fn move_value_keeps_tables() {
+        let mut dag = two_nodes();
+        dag.set_observation(0, Some(0));
+        dag.move_value(0, 0, 2);
+        assert_eq!(dag.get(0).unwrap().values, vec!["y", "z", "x"]);
+        assert_eq!(credencies(&dag, 0), vec![1.0, 2.0, 0.0]);
+        assert_eq!(credencies(&dag, 1), vec![0.5, 1.0, 0.0, -0.5, 0.0, -1.0]);
+        assert_eq!(
+            dag.get(1).unwrap().cred_description,
+            vec!["given y", "given z", "given x"]
+        );
+        assert_eq!(dag.get(0).unwrap().observation, Some(2));
+    }
+
+    #[test]
+    fn add_and_select_parent() {
+        let mut dag = two_nodes();
+        let c = dag.insert_node();
+        dag.add_value(c, "u".into());
+        dag.add_value(c, "v".into());
+        dag.add_edge(1, c).unwrap();
+        // the table does not depend on the new parent
+        let table = dag.get(1).unwrap().credencies.clone().unwrap();
+        assert_eq!(table.shape(), &[2, 3, 2]);
+        for value in 0..2 {
+            let expected = vec![0.0, 0.5, 1.0, -1.0, -0.5, 0.0];
+            let slice = table.index_axis(Axis(2), value);
+            assert_eq!(slice.iter().cloned().collect::<Vec<_>>(), expected);
+        }
+        assert_eq!(dag.get(1).unwrap().cred_description[2], "given y");
+        // keeping the credencies given one value of the removed parent undoes the addition
+        dag.remove_edge(1, c, ParentRemoval::Select(1));
+        assert_eq!(dag.get(1).unwrap().parents, vec![0]);
+        assert_eq!(credencies(&dag, 1), vec![0.0, 0.5, 1.0, -1.0, -0.5, 0.0]);
+        assert!(dag.get(c).unwrap().children.is_empty());
+    }
+
+    #[test]
+    fn marginalize_parent() {
+        let mut dag = two_nodes();
+        let table = vec![0.0, 1.0, 2.0, 0.0, 0.0, 0.0];
+        dag.set_credencies(1, ArrayD::from_shape_vec(IxDyn(&[2, 3]), table).unwrap())
+            .unwrap();
+        dag.remove_edge(1, 0, ParentRemoval::Marginalize);
+        assert!(dag.get(1).unwrap().parents.is_empty());
+        let probabilities = crate::formats::credencies_to_probabilities(
+            dag.get(1).unwrap().credencies.as_ref().unwrap(),
+        );
+        // P(b = p) = sum of P(b = p | a) P(a) over the values of a
+        let expected = (0.5 + 10.0 * 10.0 / 11.0 + 100.0 * 100.0 / 101.0) / 111.0;
+        assert!((probabilities[0] - expected).abs() < 1e-5);
+        assert_eq!(
+            dag.get(1).unwrap().cred_description,
+            vec!["given x\ngiven y\ngiven z"]
+        );
+    }
+
+    /// The exact marginal probabilities of each node, by node id
+    fn marginals(dag: &DAG) -> Vec<(usize, Vec<f32>)> {
+        let mut marginals = crate::inference::exact_beliefs(dag)
+            .unwrap()
+            .into_iter()
+            .map(|(beliefs, id)| (id, beliefs.as_probabilities().to_vec()))
+            .collect::<Vec<_>>();
+        marginals.sort_by_key(|&(id, _)| id);
+        marginals
+    }
+
+    #[test]
+    fn reversal_keeps_marginals() {
+        for json in crate::formats::tests::EXAMPLES {
+            let mut dag = DAG::from_json(json).unwrap();
+            dag.clear_observations();
+            let expected = marginals(&dag);
+            let edges = dag
+                .iter_nodes()
+                .flat_map(|(id, node)| node.parents.iter().map(move |&p| (id, p)))
+                .collect::<Vec<_>>();
+            for (child, parent) in edges {
+                let mut reversed = dag.clone();
+                if reversed.check_edge_reversal(child, parent).is_err() {
+                    continue;
+                }
+                reversed.reverse_edge(child, parent).unwrap();
+                assert!(reversed.get(parent).unwrap().parents.contains(&child));
+                assert!(!reversed.get(child).unwrap().parents.contains(&parent));
+                for ((id, e), (_, r)) in expected.iter().zip(marginals(&reversed)) {
+                    for (e, r) in e.iter().zip(&r) {
+                        assert!((e - r).abs() < 1e-4, "node {}: {} != {}", id, e, r);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reversal_of_missing_or_cyclic_edge() {
+        let mut dag = two_nodes();
+        assert!(dag.reverse_edge(0, 1).is_err());
+        // with a -> b -> c and a -> c, reversing a -> c would make a cycle
+        let c = dag.insert_node();
+        dag.add_value(c, "s".into());
+        dag.add_edge(c, 1).unwrap();
+        dag.add_edge(c, 0).unwrap();
+        match dag.check_edge_reversal(c, 0) {
+            Err(EdgeError::WouldCycle) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(dag.reverse_edge(1, 0).is_ok());
+    }
+
+    /// Observations with the label of their node instead of its id, which can change when
+    /// the network is serialized
+    fn labelled(dag: &DAG, observations: &[(usize, Evidence)]) -> Vec<(String, Evidence)> {
+        let mut labelled = observations
+            .iter()
+            .map(|&(id, ref evidence)| (dag.get(id).unwrap().label.clone(), evidence.clone()))
+            .collect::<Vec<_>>();
+        labelled.sort_by(|a, b| a.0.cmp(&b.0));
+        labelled
+    }
+
+    #[test]
+    fn link_fragment_round_trip() {
+        let mut dag = two_nodes();
+        dag.set_observation(1, Some(1));
+        dag.add_scenario("q observed".into());
+        dag.set_likelihood(0, Some(vec![0.0, 1.0, -1.0])).unwrap();
+        let fragment = dag.to_link_fragment();
+        assert!(fragment
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        let copy = DAG::from_link_fragment(&fragment).unwrap();
+        crate::formats::tests::assert_same_network(&dag, &copy, true);
+        assert_eq!(
+            labelled(&copy, &copy.observations()),
+            labelled(&dag, &dag.observations())
+        );
+        let (_, b) = copy.iter_nodes().find(|(_, n)| n.label == "b").unwrap();
+        assert_eq!(b.cred_description, vec!["given x", "given y", "given z"]);
+        assert_eq!(copy.scenarios().len(), 1);
+        assert_eq!(copy.scenarios()[0].name, "q observed");
+        // truncated or mistyped links are rejected
+        assert!(DAG::from_link_fragment(&fragment[..fragment.len() / 2]).is_err());
+        assert!(DAG::from_link_fragment("not a network").is_err());
+    }
+
+    #[test]
+    fn scenarios_round_trip_and_follow_values() {
+        let mut dag = two_nodes();
+        dag.set_observation(0, Some(2));
+        dag.set_likelihood(1, Some(vec![0.5, 0.0])).unwrap();
+        dag.add_scenario("z".into());
+        dag.clear_observations();
+        let copy = DAG::from_json(&dag.to_json()).unwrap();
+        assert_eq!(copy.scenarios().len(), 1);
+        assert_eq!(copy.scenarios()[0].name, "z");
+        assert_eq!(
+            labelled(&copy, &copy.scenarios()[0].observations),
+            labelled(&dag, &dag.scenarios()[0].observations)
+        );
+        // the observations of the scenarios follow the values of the nodes
+        dag.move_value(0, 2, 0);
+        dag.add_value(1, "r".into());
+        assert_eq!(
+            dag.scenarios()[0].observations,
+            vec![
+                (0, Evidence::Value(0)),
+                (1, Evidence::Likelihood(vec![0.5, 0.0, 0.0]))
+            ]
+        );
+        // and are dropped with the observed value or node
+        dag.remove_value(0, 0);
+        assert_eq!(
+            dag.scenarios()[0].observations,
+            vec![(1, Evidence::Likelihood(vec![0.5, 0.0, 0.0]))]
+        );
+        dag.remove_node(1);
+        assert!(dag.scenarios()[0].observations.is_empty());
+    }
+
+    #[test]
+    fn remove_node_detaches_it() {
+        // the children of a removed node are marginalized over it
+        let mut dag = two_nodes();
+        let table = vec![0.0, 1.0, 2.0, 0.0, 0.0, 0.0];
+        dag.set_credencies(1, ArrayD::from_shape_vec(IxDyn(&[2, 3]), table).unwrap())
+            .unwrap();
+        dag.remove_node(0);
+        assert!(dag.get(0).is_none());
+        let b = dag.get(1).unwrap();
+        assert!(b.parents.is_empty());
+        assert_eq!(b.cred_description, vec!["given x\ngiven y\ngiven z"]);
+        let table = dag.probability_table(1).unwrap();
+        assert_eq!(table.shape(), &[2]);
+        let expected = (0.5 + 10.0 * 10.0 / 11.0 + 100.0 * 100.0 / 101.0) / 111.0;
+        assert!((table[0] - expected).abs() < 1e-5);
+        // its parents only lose a child
+        let mut dag = two_nodes();
+        dag.remove_node(1);
+        assert!(dag.get(0).unwrap().children.is_empty());
+        assert_eq!(credencies(&dag, 0), vec![0.0, 1.0, 2.0]);
+        // and its slot is reused
+        assert_eq!(dag.insert_node(), 1);
+    }
+}
@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+
+use ndarray::IxDyn;
+
+use crate::bif::{describe, quote, Lexer, Token};
+use crate::formats::{
+    format_probability, make_identifier, probabilities_to_credencies, table_from_columns,
+    value_combinations,
+};
+use crate::graph::{DeserError, DAG};
+
+/*
+ * Import and export of the Hugin .net format
+ *
+ * Only discrete chance nodes are supported. The labels of the Hugin nodes are used as
+ * the labels of our nodes, falling back to their identifiers when empty. The identifiers
+ * are kept on the nodes and reused on export, new ones being made from the labels.
+ */
+
+const HUGIN_PUNCTUATION: &str = "{}()=;|";
+
+struct HuginNode {
+    name: String,
+    label: String,
+    description: String,
+    values: Vec<String>,
+}
+
+struct HuginPotential {
+    line: usize,
+    child: String,
+    parents: Vec<String>,
+    data: Option<Vec<f32>>,
+}
+
+fn unsupported<T>(lexer: &Lexer, construct: String) -> Result<T, DeserError> {
+    Err(DeserError::Unsupported {
+        line: lexer.line(),
+        construct,
+    })
+}
+
+/// Reads the value of an attribute whose name and '=' were already read, up to the
+/// final semicolon
+fn parse_attribute(lexer: &mut Lexer) -> Result<Vec<Token>, DeserError> {
+    let mut tokens = Vec::new();
+    let mut depth = 0;
+    loop {
+        match lexer.token()? {
+            Token::Punct(';') if depth == 0 => return Ok(tokens),
+            t => {
+                match t {
+                    Token::Punct('(') => depth += 1,
+                    Token::Punct(')') => depth -= 1,
+                    _ => {}
+                }
+                tokens.push(t);
+            }
+        }
+    }
+}
+
+fn string_value(lexer: &Lexer, attribute: &str, value: &[Token]) -> Result<String, DeserError> {
+    match value {
+        [Token::Str(s)] => Ok(s.clone()),
+        _ => lexer.error(format!("the {} must be a string", attribute)),
+    }
+}
+
+fn parse_node(lexer: &mut Lexer) -> Result<HuginNode, DeserError> {
+    let name = lexer.name()?;
+    let mut node = HuginNode {
+        label: String::new(),
+        description: String::new(),
+        values: Vec::new(),
+        name,
+    };
+    lexer.expect('{')?;
+    loop {
+        let attribute = match lexer.token()? {
+            Token::Punct('}') => break,
+            Token::Word(w) => w,
+            t => return lexer.error(format!("unexpected {} in node", describe(&t))),
+        };
+        lexer.expect('=')?;
+        let value = parse_attribute(lexer)?;
+        match &attribute[..] {
+            "label" => node.label = string_value(lexer, &attribute, &value)?,
+            "description" => node.description = string_value(lexer, &attribute, &value)?,
+            "states" => {
+                for token in value {
+                    match token {
+                        Token::Str(s) => node.values.push(s),
+                        Token::Punct('(') | Token::Punct(')') => {}
+                        t => return lexer.error(format!("unexpected {} in states", describe(&t))),
+                    }
+                }
+            }
+            "subtype" => match &value[..] {
+                [Token::Word(w)] if w == "labelled" => {}
+                _ => return unsupported(lexer, "node subtypes".into()),
+            },
+            _ => {}
+        }
+    }
+    if node.label.is_empty() {
+        node.label = node.name.clone();
+    }
+    Ok(node)
+}
+
+fn parse_potential(lexer: &mut Lexer) -> Result<HuginPotential, DeserError> {
+    let line = lexer.line();
+    lexer.expect('(')?;
+    let mut names = Vec::new();
+    let mut parents = Vec::new();
+    let mut conditioned = false;
+    loop {
+        match lexer.token()? {
+            Token::Punct(')') => break,
+            Token::Punct('|') => conditioned = true,
+            Token::Word(w) if conditioned => parents.push(w),
+            Token::Word(w) => names.push(w),
+            t => return lexer.error(format!("unexpected {} in potential", describe(&t))),
+        }
+    }
+    if names.len() != 1 {
+        return unsupported(lexer, "potentials not about exactly one node".into());
+    }
+    let mut potential = HuginPotential {
+        line,
+        child: names.remove(0),
+        parents,
+        data: None,
+    };
+    let mut has_model = false;
+    lexer.expect('{')?;
+    loop {
+        let attribute = match lexer.token()? {
+            Token::Punct('}') => break,
+            Token::Word(w) => w,
+            t => return lexer.error(format!("unexpected {} in potential", describe(&t))),
+        };
+        lexer.expect('=')?;
+        let value = parse_attribute(lexer)?;
+        match &attribute[..] {
+            "data" => {
+                let mut data = Vec::new();
+                for token in value {
+                    match token {
+                        Token::Word(ref w) => match w.parse() {
+                            Ok(v) => data.push(v),
+                            Err(_) => {
+                                return lexer.error(format!("expected a number, found '{}'", w))
+                            }
+                        },
+                        Token::Punct('(') | Token::Punct(')') => {}
+                        t => return lexer.error(format!("unexpected {} in data", describe(&t))),
+                    }
+                }
+                potential.data = Some(data);
+            }
+            "model_data" => has_model = true,
+            _ => {}
+        }
+    }
+    if has_model && potential.data.is_none() {
+        return unsupported(lexer, "potentials defined by expressions".into());
+    }
+    Ok(potential)
+}
+
+impl DAG {
+    pub fn from_hugin_net(text: &str) -> Result<DAG, DeserError> {
+        let mut lexer = Lexer::new(text, HUGIN_PUNCTUATION, "%");
+        let mut nodes = Vec::new();
+        let mut potentials = Vec::new();
+        while let Some(token) = lexer.next_token()? {
+            let keyword = match token {
+                Token::Word(w) => w,
+                t => return lexer.error(format!("unexpected {}", describe(&t))),
+            };
+            match &keyword[..] {
+                "net" => {
+                    lexer.expect('{')?;
+                    lexer.skip_block('{', '}')?;
+                }
+                "node" => nodes.push(parse_node(&mut lexer)?),
+                "discrete" => match lexer.token()? {
+                    Token::Word(ref w) if w == "node" => nodes.push(parse_node(&mut lexer)?),
+                    t => return lexer.error(format!("unexpected {}", describe(&t))),
+                },
+                "continuous" | "decision" | "utility" | "function" => {
+                    return unsupported(&lexer, format!("{} nodes", keyword))
+                }
+                "potential" => potentials.push(parse_potential(&mut lexer)?),
+                "class" => return unsupported(&lexer, "classes".into()),
+                _ => return lexer.error(format!("unexpected '{}'", keyword)),
+            }
+        }
+
+        let mut dag = DAG::new();
+        for node in &nodes {
+            let id = dag.insert_node();
+            dag.set_label(id, node.label.clone());
+            dag.set_identifier(id, Some(node.name.clone()));
+            dag.set_description(id, node.description.clone());
+            for value in &node.values {
+                dag.add_value(id, value.clone());
+            }
+        }
+        for potential in &potentials {
+            let error = |message: String| {
+                Err(DeserError::Syntax {
+                    line: potential.line,
+                    message,
+                })
+            };
+            let mut ids = Vec::new();
+            for name in Some(&potential.child).into_iter().chain(&potential.parents) {
+                match nodes.iter().position(|n| &n.name == name) {
+                    Some(id) => ids.push(id),
+                    None => return error(format!("unknown node '{}'", name)),
+                }
+            }
+            for &parent in &ids[1..] {
+                dag.add_edge(ids[0], parent).map_err(DeserError::Graph)?;
+            }
+            let data = match potential.data {
+                Some(ref data) => data,
+                None => continue,
+            };
+
+            let mut shape = vec![nodes[ids[0]].values.len()];
+            shape.extend(ids[1..].iter().map(|&p| nodes[p].values.len()));
+            if shape[0] == 0 {
+                return error(format!("node '{}' has no states", potential.child));
+            }
+            if data.len() != shape.iter().product::<usize>() {
+                return error(format!(
+                    "wrong number of data values for '{}'",
+                    potential.child
+                ));
+            }
+            let table = table_from_columns(&shape, data);
+            let _ = dag.set_credencies(ids[0], probabilities_to_credencies(&table));
+        }
+        Ok(dag)
+    }
+
+    pub fn to_hugin_net(&self) -> String {
+        let order = self.topological_order();
+        let mut taken = Vec::new();
+        let mut names = HashMap::new();
+        // keep the identifiers of the imported nodes, unless two of them clash
+        for (id, node) in self.iter_nodes() {
+            if let Some(ref identifier) = node.identifier {
+                if !taken.contains(identifier) {
+                    taken.push(identifier.clone());
+                    names.insert(id, identifier.clone());
+                }
+            }
+        }
+        for (id, node) in self.iter_nodes() {
+            if !names.contains_key(&id) {
+                names.insert(id, make_identifier(&node.label, "node", &mut taken));
+            }
+        }
+
+        let mut net = String::from("net\n{\n}\n");
+        for &id in &order {
+            let node = self.get(id).unwrap();
+            net += &format!("\nnode {}\n{{\n", names[&id]);
+            net += &format!("    label = {};\n", quote(&node.label));
+            let states = node.values.iter().map(|v| quote(v)).collect::<Vec<_>>();
+            net += &format!("    states = ({});\n", states.join(" "));
+            if !node.description.is_empty() {
+                net += &format!("    description = {};\n", quote(&node.description));
+            }
+            net += "}\n";
+        }
+        for &id in &order {
+            let node = self.get(id).unwrap();
+            let probabilities = self.probability_table(id).unwrap();
+            let shape = probabilities.shape().to_vec();
+            if node.parents.is_empty() {
+                net += &format!("\npotential ({})\n{{\n", names[&id]);
+            } else {
+                let parents = node
+                    .parents
+                    .iter()
+                    .map(|p| &names[p][..])
+                    .collect::<Vec<_>>();
+                net += &format!("\npotential ({} | {})\n{{\n", names[&id], parents.join(" "));
+            }
+            // nest the probabilities of the child in one level of parentheses per parent
+            let mut data = String::new();
+            let combinations = value_combinations(&shape[1..]).collect::<Vec<_>>();
+            for (i, combination) in combinations.iter().enumerate() {
+                let opened = match i {
+                    0 => combination.len(),
+                    _ => combination.iter().rev().take_while(|&&v| v == 0).count(),
+                };
+                let closed = match combinations.get(i + 1) {
+                    None => combination.len(),
+                    Some(next) => next.iter().rev().take_while(|&&v| v == 0).count(),
+                };
+                if i > 0 {
+                    data += &" ".repeat(11 + combination.len() - opened);
+                }
+                let column = (0..node.values.len())
+                    .map(|v| {
+                        let mut index = vec![v];
+                        index.extend(combination);
+                        format_probability(probabilities[IxDyn(&index)])
+                    })
+                    .collect::<Vec<_>>();
+                data += &format!(
+                    "{}({}){}",
+                    "(".repeat(opened),
+                    column.join(" "),
+                    ")".repeat(closed)
+                );
+                if i + 1 < combinations.len() {
+                    data += "\n";
+                }
+            }
+            net += &format!("    data = {};\n}}\n", data);
+        }
+        net
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::credencies_to_probabilities;
+    use crate::formats::tests::{assert_same_network, EXAMPLES};
+
+    #[test]
+    fn round_trip_examples() {
+        for json in EXAMPLES {
+            let dag = DAG::from_json(json).unwrap();
+            let copy = DAG::from_hugin_net(&dag.to_hugin_net()).unwrap();
+            assert_same_network(&dag, &copy, true);
+        }
+    }
+
+    #[test]
+    fn identifiers_are_kept() {
+        let net = "net { }
+            node fo { label = \"Family out\"; states = (\"t\" \"f\"); }
+            node lo { states = (\"t\" \"f\"); }
+            potential (fo) { data = (0.15 0.85); }
+            potential (lo | fo) { data = ((0.6 0.4) (0.05 0.95)); }";
+        let mut dag = DAG::from_hugin_net(net).unwrap();
+        let (_, lo) = dag.iter_nodes().find(|(_, n)| n.label == "lo").unwrap();
+        let table = credencies_to_probabilities(lo.credencies.as_ref().unwrap());
+        assert!((table[IxDyn(&[0, 1])] - 0.05).abs() < 1e-6);
+        // a new node and a renamed one
+        let id = dag.insert_node();
+        dag.set_label(id, "Family out".into());
+        dag.add_value(id, "yes".into());
+        dag.set_label(0, "Renamed".into());
+        let exported = dag.to_hugin_net();
+        assert!(exported.contains("node fo\n"));
+        assert!(exported.contains("node lo\n"));
+        assert!(exported.contains("potential (lo | fo)"));
+        assert!(exported.contains("node Family_out\n"));
+        // the identifiers are saved with the network
+        let json = DAG::from_json(&dag.to_json()).unwrap().to_hugin_net();
+        assert!(json.contains("node fo\n") && json.contains("node Family_out\n"));
+    }
+
+    #[test]
+    fn unsupported_nodes() {
+        match DAG::from_hugin_net("net { } continuous node x { }") {
+            Err(DeserError::Unsupported { .. }) => {}
+            _ => panic!("continuous nodes must be reported as unsupported"),
+        }
+    }
+}
@@ -0,0 +1,687 @@
+use std::cmp::Ordering;
+
+use loopybayesnet::LogProbVector;
+use ndarray::{Array1, ArrayD, ArrayView1, Axis, IxDyn};
+
+use crate::formats::value_combinations;
+use crate::graph::DAG;
+
+/*
+ * Exact inference on the network, using variable elimination
+ */
+
+pub fn log_sum_exp_vec(x: ArrayView1<f32>) -> f32 {
+    let max_log = x.fold(std::f32::NEG_INFINITY, |old_max, &v| f32::max(old_max, v));
+    if !max_log.is_finite() {
+        // if max_log is +inf, result will be +inf anyway
+        // if max_log is -inf, then all log values are -inf, and the result of the log_sum_exp is too
+        max_log
+    } else {
+        max_log + x.mapv(|v| (v - max_log).exp()).sum().ln()
+    }
+}
+
+/// A function of the values of some nodes of the network, stored as natural logarithms
+#[derive(Clone, Debug)]
+pub struct Factor {
+    /// The nodes this factor depends on, in the order of the axes of `values`
+    pub vars: Vec<usize>,
+    pub values: ArrayD<f32>,
+}
+
+impl Factor {
+    /// Rearranges the values of this factor along the given nodes, which must include all the
+    /// nodes of the factor. `shape` contains the number of values of each of these nodes.
+    pub fn expand(&self, vars: &[usize], shape: &[usize]) -> ArrayD<f32> {
+        // reorder the axes to follow the order of `vars`
+        let mut axes = (0..self.vars.len()).collect::<Vec<_>>();
+        axes.sort_by_key(|&a| vars.iter().position(|&v| v == self.vars[a]).unwrap());
+        let permuted = self.values.view().permuted_axes(IxDyn(&axes));
+        // and insert the missing nodes as axes of length 1 to be broadcasted
+        let unit_shape = vars
+            .iter()
+            .zip(shape)
+            .map(|(v, &n)| if self.vars.contains(v) { n } else { 1 })
+            .collect::<Vec<_>>();
+        let data = permuted.iter().cloned().collect();
+        let array = ArrayD::from_shape_vec(IxDyn(&unit_shape), data).unwrap();
+        array.broadcast(IxDyn(shape)).unwrap().to_owned()
+    }
+
+    /// Multiplies factors together, `sizes[n]` being the number of values of node `n`
+    pub fn product(factors: &[Factor], sizes: &[usize]) -> Factor {
+        let mut vars = Vec::new();
+        for factor in factors {
+            for &v in &factor.vars {
+                if !vars.contains(&v) {
+                    vars.push(v);
+                }
+            }
+        }
+        let shape = vars.iter().map(|&v| sizes[v]).collect::<Vec<_>>();
+        let mut values = ArrayD::zeros(IxDyn(&shape));
+        for factor in factors {
+            values += &factor.expand(&vars, &shape);
+        }
+        Factor { vars, values }
+    }
+
+    /// Sums this factor over all the values of a node
+    pub fn sum_out(&self, var: usize) -> Factor {
+        let axis = match self.vars.iter().position(|&v| v == var) {
+            Some(axis) => axis,
+            None => return self.clone(),
+        };
+        let mut vars = self.vars.clone();
+        vars.remove(axis);
+        Factor {
+            vars,
+            values: self.values.map_axis(Axis(axis), log_sum_exp_vec),
+        }
+    }
+}
+
+/// Builds the factors describing the network and its observations
+///
+/// Also returns the number of values of each node, indexed by node id. Fails if a node
+/// has no values.
+pub fn network_factors(dag: &DAG) -> Result<(Vec<Factor>, Vec<usize>), ()> {
+    let mut sizes = Vec::new();
+    for (id, node) in dag.iter_nodes() {
+        if node.values.is_empty() {
+            return Err(());
+        }
+        if sizes.len() <= id {
+            sizes.resize(id + 1, 0);
+        }
+        sizes[id] = node.values.len();
+    }
+
+    let mut factors = Vec::new();
+    for (id, node) in dag.iter_nodes() {
+        let mut vars = vec![id];
+        vars.extend(&node.parents);
+        let shape = vars.iter().map(|&v| sizes[v]).collect::<Vec<_>>();
+        let mut values = node
+            .credencies
+            .as_ref()
+            .map(|array| array * 10f32.ln())
+            .unwrap_or_else(|| ArrayD::zeros(IxDyn(&shape)));
+        // normalize the distribution of the node for each combination of parent values
+        for mut lane in values.lanes_mut(Axis(0)) {
+            let norm = log_sum_exp_vec(lane.view());
+            lane.mapv_inplace(|v| v - norm);
+        }
+        factors.push(Factor { vars, values });
+
+        if let Some(obs) = node.observation {
+            let mut values = ArrayD::from_elem(IxDyn(&[sizes[id]]), std::f32::NEG_INFINITY);
+            if obs < sizes[id] {
+                values[obs] = 0.0;
+            }
+            factors.push(Factor {
+                vars: vec![id],
+                values,
+            });
+        }
+        // uncertain or negative evidence weights the values of the node
+        if let Some(weights) = node.finding_log_weights() {
+            factors.push(Factor {
+                vars: vec![id],
+                values: ArrayD::from_shape_vec(IxDyn(&[sizes[id]]), weights).map_err(|_| ())?,
+            });
+        }
+    }
+
+    Ok((factors, sizes))
+}
+
+/// Picks the next node to eliminate among the ones of the factors with the given
+/// `scopes`, except the ones of `keep`
+fn next_elimination(scopes: &[&[usize]], keep: &[usize], sizes: &[usize]) -> Option<usize> {
+    let mut candidates = Vec::new();
+    for scope in scopes {
+        for &v in scope.iter() {
+            if !keep.contains(&v) && !candidates.contains(&v) {
+                candidates.push(v);
+            }
+        }
+    }
+    // eliminate first the node whose elimination creates the smallest factor
+    candidates.into_iter().min_by_key(|&var| {
+        scopes
+            .iter()
+            .filter(|scope| scope.contains(&var))
+            .flat_map(|scope| scope.iter().cloned())
+            .fold(Vec::new(), |mut vars, v| {
+                if !vars.contains(&v) {
+                    vars.push(v);
+                }
+                vars
+            })
+            .iter()
+            .fold(1usize, |size, &v| size.saturating_mul(sizes[v]))
+    })
+}
+
+/// Number of factor entries above which exact inference is only run when selected, as
+/// it could then freeze the page for a long time
+pub const EXACT_INFERENCE_BUDGET: usize = 10_000_000;
+
+/// The number of values of each node, indexed by node id, and the nodes each factor of
+/// the network depends on
+fn network_scopes(dag: &DAG) -> (Vec<usize>, Vec<Vec<usize>>) {
+    let mut sizes = Vec::new();
+    let mut scopes = Vec::new();
+    for (id, node) in dag.iter_nodes() {
+        if sizes.len() <= id {
+            sizes.resize(id + 1, 0);
+        }
+        sizes[id] = node.values.len();
+        let mut scope = vec![id];
+        scope.extend(&node.parents);
+        scopes.push(scope);
+    }
+    (sizes, scopes)
+}
+
+/// Simulates the elimination of all the nodes of `scopes` but the ones of `keep`, and
+/// returns the scopes of the remaining factors
+///
+/// `step` is given the number of entries of each product of factors, and the number of
+/// factors multiplied.
+fn simulate_elimination<F: FnMut(usize, usize)>(
+    mut scopes: Vec<Vec<usize>>,
+    keep: &[usize],
+    sizes: &[usize],
+    mut step: F,
+) -> Vec<Vec<usize>> {
+    loop {
+        let var = {
+            let views = scopes.iter().map(|s| &s[..]).collect::<Vec<_>>();
+            match next_elimination(&views, keep, sizes) {
+                Some(var) => var,
+                None => break,
+            }
+        };
+        let (involved, rest): (Vec<_>, Vec<_>) = scopes.into_iter().partition(|s| s.contains(&var));
+        let mut product = Vec::new();
+        for &v in involved.iter().flatten() {
+            if !product.contains(&v) {
+                product.push(v);
+            }
+        }
+        let entries = product
+            .iter()
+            .fold(1usize, |size, &v| size.saturating_mul(sizes[v]));
+        step(entries, involved.len());
+        product.retain(|&v| v != var);
+        scopes = rest;
+        scopes.push(product);
+    }
+    scopes
+}
+
+/// Estimates the work of computing the exact beliefs of the network, as the number of
+/// factor entries built by variable elimination
+pub fn exact_inference_cost(dag: &DAG) -> usize {
+    let (sizes, scopes) = network_scopes(dag);
+    // eliminate all the nodes in turn, as done for each node by `exact_beliefs`
+    let mut cost = 0usize;
+    simulate_elimination(scopes, &[], &sizes, |entries, _| {
+        cost = cost.saturating_add(entries)
+    });
+    cost.saturating_mul(dag.iter_nodes().count())
+}
+
+/// Estimates the work of finding the `count` most probable explanations of the
+/// `explained` nodes, comparable to `exact_inference_cost`
+///
+/// The other nodes are summed out first, which can build much larger factors than the
+/// elimination order of `exact_beliefs`, and each entry of a max-product factor then
+/// combines up to `count` assignments of each multiplied factor.
+pub fn explanation_cost(dag: &DAG, explained: &[usize], count: usize) -> usize {
+    let (sizes, scopes) = network_scopes(dag);
+    let explained = explained_nodes(dag, explained);
+    let mut cost = 0usize;
+    let scopes = simulate_elimination(scopes, &explained, &sizes, |entries, _| {
+        cost = cost.saturating_add(entries)
+    });
+    let combinations = count.saturating_mul(count);
+    simulate_elimination(scopes, &[], &sizes, |entries, factors| {
+        cost = cost.saturating_add(entries.saturating_mul(factors).saturating_mul(combinations))
+    });
+    cost
+}
+
+/// Sums the factors over all the nodes except the ones of `keep`, without multiplying
+/// the remaining factors together
+pub fn sum_out_all(mut factors: Vec<Factor>, keep: &[usize], sizes: &[usize]) -> Vec<Factor> {
+    loop {
+        let scopes = factors.iter().map(|f| &f.vars[..]).collect::<Vec<_>>();
+        let var = match next_elimination(&scopes, keep, sizes) {
+            Some(var) => var,
+            None => break,
+        };
+        let (involved, rest): (Vec<_>, Vec<_>) =
+            factors.into_iter().partition(|f| f.vars.contains(&var));
+        factors = rest;
+        factors.push(Factor::product(&involved, sizes).sum_out(var));
+    }
+    factors
+}
+
+/// Sums the product of the factors over all the nodes except the ones of `keep`
+pub fn eliminate(factors: Vec<Factor>, keep: &[usize], sizes: &[usize]) -> Factor {
+    Factor::product(&sum_out_all(factors, keep, sizes), sizes)
+}
+
+/// Computes the exact posterior beliefs of all the nodes of the network given the observations
+///
+/// The results are given in the same format and order as the loopy belief propagation.
+pub fn exact_beliefs(dag: &DAG) -> Result<Vec<(LogProbVector, usize)>, ()> {
+    let (factors, sizes) = network_factors(dag)?;
+    Ok(dag
+        .topological_order()
+        .into_iter()
+        .map(|id| {
+            let marginal = eliminate(factors.clone(), &[id], &sizes);
+            let values = marginal.expand(&[id], &[sizes[id]]);
+            let mut beliefs =
+                LogProbVector::from_log_probabilities(values.into_shape((sizes[id],)).unwrap());
+            beliefs.renormalize();
+            (beliefs, id)
+        })
+        .collect())
+}
+
+/// Computes the natural logarithm of the probability of the observations
+pub fn log_evidence(dag: &DAG) -> Result<f32, ()> {
+    let (factors, sizes) = network_factors(dag)?;
+    let total = eliminate(factors, &[], &sizes);
+    Ok(total.values.iter().cloned().next().unwrap_or(0.0))
+}
+
+/// Computes, for each value of the unobserved `node`, the decimal logarithm of the Bayes
+/// factor of the observations for this value against the other ones:
+/// P(observations | value) / P(observations | other values)
+pub fn evidence_weights(dag: &DAG, node: usize) -> Result<Vec<f32>, ()> {
+    // log P(value, observations) and log P(value) for each value of the node
+    let joint_log_probabilities = |dag: &DAG| -> Result<Vec<f32>, ()> {
+        let (factors, sizes) = network_factors(dag)?;
+        let joint = eliminate(factors, &[node], &sizes);
+        Ok(joint
+            .expand(&[node], &[sizes[node]])
+            .iter()
+            .cloned()
+            .collect())
+    };
+    let mut unobserved = dag.clone();
+    unobserved.clear_observations();
+    let posterior = joint_log_probabilities(dag)?;
+    let prior = joint_log_probabilities(&unobserved)?;
+    // log of the sum of the probabilities of all the values but one
+    let others = |logs: &[f32], value: usize| {
+        let mut logs = Array1::from_vec(logs.to_vec());
+        logs[value] = std::f32::NEG_INFINITY;
+        log_sum_exp_vec(logs.view())
+    };
+    Ok((0..posterior.len())
+        .map(|v| {
+            let for_value = posterior[v] - prior[v];
+            let against_value = others(&posterior, v) - others(&prior, v);
+            (for_value - against_value) / 10f32.ln()
+        })
+        .collect())
+}
+
+/// A joint assignment of values to some nodes of the network
+#[derive(Clone, Debug)]
+pub struct Explanation {
+    /// Natural logarithm of the probability of the assignment jointly with the observations
+    pub log_probability: f32,
+    /// The value of each explained node, as pairs of node id and value
+    pub values: Vec<(usize, usize)>,
+}
+
+/// Keeps the `count` most probable of the possible explanations
+fn keep_best(mut explanations: Vec<Explanation>, count: usize) -> Vec<Explanation> {
+    explanations.retain(|e| e.log_probability > std::f32::NEG_INFINITY);
+    explanations.sort_by(|a, b| {
+        b.log_probability
+            .partial_cmp(&a.log_probability)
+            .unwrap_or(Ordering::Equal)
+    });
+    explanations.truncate(count);
+    explanations
+}
+
+/// Index of a combination of values of `vars` in a factor over them
+fn flat_index<F: Fn(usize) -> usize>(vars: &[usize], sizes: &[usize], value: F) -> usize {
+    vars.iter().fold(0, |index, &v| index * sizes[v] + value(v))
+}
+
+/// A factor of max-product elimination: for each combination of the values of its nodes,
+/// it keeps the best assignments of the nodes already maximized out
+struct MaxFactor {
+    vars: Vec<usize>,
+    entries: Vec<Vec<Explanation>>,
+}
+
+impl MaxFactor {
+    fn from_factor(factor: &Factor) -> MaxFactor {
+        MaxFactor {
+            vars: factor.vars.clone(),
+            entries: factor
+                .values
+                .iter()
+                .map(|&log_probability| {
+                    keep_best(
+                        vec![Explanation {
+                            log_probability,
+                            values: Vec::new(),
+                        }],
+                        1,
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Multiplies factors together, keeping the `count` best assignments for each
+    /// combination of values
+    fn product(factors: &[MaxFactor], sizes: &[usize], count: usize) -> MaxFactor {
+        let mut vars = Vec::new();
+        for factor in factors {
+            for &v in &factor.vars {
+                if !vars.contains(&v) {
+                    vars.push(v);
+                }
+            }
+        }
+        let shape = vars.iter().map(|&v| sizes[v]).collect::<Vec<_>>();
+        let entries = value_combinations(&shape)
+            .map(|combination| {
+                let value = |var: usize| combination[vars.iter().position(|&v| v == var).unwrap()];
+                let empty = Explanation {
+                    log_probability: 0.0,
+                    values: Vec::new(),
+                };
+                factors.iter().fold(vec![empty], |best, factor| {
+                    let entry = &factor.entries[flat_index(&factor.vars, sizes, &value)];
+                    let combined = best
+                        .iter()
+                        .flat_map(|a| {
+                            entry.iter().map(move |b| Explanation {
+                                log_probability: a.log_probability + b.log_probability,
+                                values: a.values.iter().chain(&b.values).cloned().collect(),
+                            })
+                        })
+                        .collect();
+                    keep_best(combined, count)
+                })
+            })
+            .collect();
+        MaxFactor { vars, entries }
+    }
+
+    /// Maximizes this factor over all the values of a node, keeping the `count` best
+    /// assignments for each combination of the values of the other nodes
+    fn max_out(&self, var: usize, sizes: &[usize], count: usize) -> MaxFactor {
+        let axis = match self.vars.iter().position(|&v| v == var) {
+            Some(axis) => axis,
+            None => {
+                return MaxFactor {
+                    vars: self.vars.clone(),
+                    entries: self.entries.clone(),
+                }
+            }
+        };
+        let mut vars = self.vars.clone();
+        vars.remove(axis);
+        let shape = vars.iter().map(|&v| sizes[v]).collect::<Vec<_>>();
+        let entries = value_combinations(&shape)
+            .map(|mut combination| {
+                combination.insert(axis, 0);
+                let mut candidates = Vec::new();
+                for value in 0..sizes[var] {
+                    combination[axis] = value;
+                    let index = flat_index(&self.vars, sizes, |v| {
+                        combination[self.vars.iter().position(|&w| w == v).unwrap()]
+                    });
+                    candidates.extend(self.entries[index].iter().map(|e| {
+                        let mut e = e.clone();
+                        e.values.push((var, value));
+                        e
+                    }));
+                }
+                keep_best(candidates, count)
+            })
+            .collect();
+        MaxFactor { vars, entries }
+    }
+}
+
+/// Largest number of explanations that can be asked for
+pub const MAX_EXPLANATIONS: usize = 100;
+
+/// The unobserved nodes among `explained`, or all the unobserved nodes if there are none
+fn explained_nodes(dag: &DAG, explained: &[usize]) -> Vec<usize> {
+    let unobserved = |id: &usize| dag.get(*id).map_or(false, |n| n.observation.is_none());
+    let explained = explained
+        .iter()
+        .cloned()
+        .filter(unobserved)
+        .collect::<Vec<_>>();
+    if explained.is_empty() {
+        dag.iter_nodes()
+            .map(|(id, _)| id)
+            .filter(unobserved)
+            .collect()
+    } else {
+        explained
+    }
+}
+
+/// Finds the `count` most probable joint assignments of the `explained` nodes given the
+/// observations, summing over the values of the other nodes (MAP)
+///
+/// When `explained` is empty, all the unobserved nodes are explained (MPE). The explanations
+/// are sorted from the most probable, and their values follow the topological order.
+pub fn most_probable_explanations(
+    dag: &DAG,
+    explained: &[usize],
+    count: usize,
+) -> Result<Vec<Explanation>, ()> {
+    let (factors, sizes) = network_factors(dag)?;
+    let explained = explained_nodes(dag, explained);
+
+    // the other nodes must all be summed out before maximizing over the explained ones
+    let mut factors = sum_out_all(factors, &explained, &sizes)
+        .iter()
+        .map(MaxFactor::from_factor)
+        .collect::<Vec<_>>();
+    loop {
+        let scopes = factors.iter().map(|f| &f.vars[..]).collect::<Vec<_>>();
+        let var = match next_elimination(&scopes, &[], &sizes) {
+            Some(var) => var,
+            None => break,
+        };
+        let (involved, rest): (Vec<_>, Vec<_>) =
+            factors.into_iter().partition(|f| f.vars.contains(&var));
+        factors = rest;
+        factors.push(MaxFactor::product(&involved, &sizes, count).max_out(var, &sizes, count));
+    }
+    let mut explanations = MaxFactor::product(&factors, &sizes, count)
+        .entries
+        .pop()
+        .unwrap_or_default();
+
+    let order = dag.topological_order();
+    for explanation in &mut explanations {
+        explanation
+            .values
+            .sort_by_key(|&(id, _)| order.iter().position(|&o| o == id));
+    }
+    Ok(explanations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::credencies_to_probabilities;
+
+    /// Builds the network a -> b, a -> c, b -> d, c -> d, whose loop makes loopy belief
+    /// propagation approximate, with d observed
+    fn diamond() -> DAG {
+        let mut dag = DAG::new();
+        let sizes = [2, 3, 2, 2];
+        for (id, &size) in sizes.iter().enumerate() {
+            assert_eq!(dag.insert_node(), id);
+            for v in 0..size {
+                dag.add_value(id, v.to_string());
+            }
+        }
+        for &(child, parent) in &[(1, 0), (2, 0), (3, 1), (3, 2)] {
+            dag.add_edge(child, parent).unwrap();
+        }
+        let tables: [(&[usize], &[f32]); 4] = [
+            (&[2], &[0.3, -0.2]),
+            (&[3, 2], &[0.5, -1.0, 0.0, 0.2, -0.4, 0.8]),
+            (&[2, 2], &[1.0, -0.5, 0.0, 0.7]),
+            (
+                &[2, 3, 2],
+                &[
+                    0.1, 0.9, -0.3, 0.4, 1.2, -0.8, 0.0, 0.5, 0.6, -1.1, 0.3, 0.2,
+                ],
+            ),
+        ];
+        for (id, &(shape, table)) in tables.iter().enumerate() {
+            let table = ArrayD::from_shape_vec(IxDyn(shape), table.to_vec()).unwrap();
+            dag.set_credencies(id, table).unwrap();
+        }
+        dag.set_observation(3, Some(1));
+        dag
+    }
+
+    /// Lists every assignment of values to the nodes, indexed by node id, along with its
+    /// probability jointly with the observations
+    fn enumerate(dag: &DAG) -> Vec<(Vec<usize>, f64)> {
+        let sizes = dag
+            .iter_nodes()
+            .map(|(_, node)| node.values.len())
+            .collect::<Vec<_>>();
+        value_combinations(&sizes)
+            .map(|assignment| {
+                let mut probability = 1.0f64;
+                for (id, node) in dag.iter_nodes() {
+                    let table = credencies_to_probabilities(node.credencies.as_ref().unwrap());
+                    let mut index = vec![assignment[id]];
+                    index.extend(node.parents.iter().map(|&p| assignment[p]));
+                    probability *= f64::from(table[IxDyn(&index)]);
+                    if let Some(weights) = node.finding_log_weights() {
+                        probability *= f64::from(weights[assignment[id]]).exp();
+                    }
+                    if node.observation.map_or(false, |obs| obs != assignment[id]) {
+                        probability = 0.0;
+                    }
+                }
+                (assignment, probability)
+            })
+            .collect()
+    }
+
+    /// The probability of each value of `node` jointly with the observations
+    fn enumerated_marginal(dag: &DAG, node: usize) -> Vec<f64> {
+        let mut marginal = vec![0.0; dag.get(node).unwrap().values.len()];
+        for (assignment, probability) in enumerate(dag) {
+            marginal[assignment[node]] += probability;
+        }
+        marginal
+    }
+
+    #[test]
+    fn exact_beliefs_match_enumeration() {
+        let mut dag = diamond();
+        let check = |dag: &DAG| {
+            for (beliefs, id) in exact_beliefs(dag).unwrap() {
+                let marginal = enumerated_marginal(dag, id);
+                let total = marginal.iter().sum::<f64>();
+                for (b, m) in beliefs.as_probabilities().iter().zip(&marginal) {
+                    assert!((f64::from(*b) - m / total).abs() < 1e-5, "node {}", id);
+                }
+            }
+        };
+        check(&dag);
+        // with uncertain and negative evidence
+        dag.set_likelihood(2, Some(vec![0.4, -0.3])).unwrap();
+        dag.set_allowed(1, vec![true, false, true]).unwrap();
+        check(&dag);
+    }
+
+    #[test]
+    fn mpe_matches_enumeration() {
+        let dag = diamond();
+        let mut expected = enumerate(&dag);
+        expected.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+        let explanations = most_probable_explanations(&dag, &[], 4).unwrap();
+        assert_eq!(explanations.len(), 4);
+        for (explanation, (assignment, probability)) in explanations.iter().zip(&expected) {
+            assert!((f64::from(explanation.log_probability) - probability.ln()).abs() < 1e-4);
+            for &(id, value) in &explanation.values {
+                assert_eq!(assignment[id], value);
+            }
+            // the observed node is not explained
+            assert_eq!(explanation.values.len(), 3);
+        }
+    }
+
+    #[test]
+    fn map_matches_enumeration() {
+        let dag = diamond();
+        // the most probable values of a and c, summing over b
+        let mut expected = vec![vec![0.0f64; 2]; 2];
+        for (assignment, probability) in enumerate(&dag) {
+            expected[assignment[0]][assignment[2]] += probability;
+        }
+        let explanations = most_probable_explanations(&dag, &[2, 0], 4).unwrap();
+        assert_eq!(explanations.len(), 4);
+        let mut previous = std::f32::INFINITY;
+        for explanation in &explanations {
+            let mut values = explanation.values.clone();
+            values.sort();
+            assert_eq!(
+                values.iter().map(|&(id, _)| id).collect::<Vec<_>>(),
+                vec![0, 2]
+            );
+            let probability = expected[values[0].1][values[1].1];
+            assert!((f64::from(explanation.log_probability) - probability.ln()).abs() < 1e-4);
+            assert!(explanation.log_probability <= previous);
+            previous = explanation.log_probability;
+        }
+    }
+
+    #[test]
+    fn explanation_cost_grows_with_count() {
+        let dag = diamond();
+        let one = explanation_cost(&dag, &[], 1);
+        let ten = explanation_cost(&dag, &[], 10);
+        // only the maximization depends on the number of explanations
+        assert!(one > 0 && ten > 50 * one && ten < 100 * one);
+        assert!(explanation_cost(&dag, &[], MAX_EXPLANATIONS) < EXACT_INFERENCE_BUDGET);
+    }
+
+    #[test]
+    fn log_evidence_matches_enumeration() {
+        let mut dag = diamond();
+        let total = enumerate(&dag).iter().map(|(_, p)| p).sum::<f64>();
+        assert!((f64::from(log_evidence(&dag).unwrap()) - total.ln()).abs() < 1e-4);
+        dag.set_observation(1, Some(2));
+        let total = enumerate(&dag).iter().map(|(_, p)| p).sum::<f64>();
+        assert!((f64::from(log_evidence(&dag).unwrap()) - total.ln()).abs() < 1e-4);
+        // without observations, the evidence is certain
+        dag.set_observation(1, None);
+        dag.set_observation(3, None);
+        assert!(log_evidence(&dag).unwrap().abs() < 1e-5);
+    }
+}
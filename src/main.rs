@@ -1,14 +1,22 @@
 #![recursion_limit = "512"]
 
+mod bif;
 mod draw;
 mod editor;
+mod files;
+mod formats;
 mod graph;
+mod hugin;
 mod i18n;
+mod inference;
 mod markdown;
 mod model;
 mod render;
+mod report;
 mod results;
+mod storage;
 mod ui;
+mod xmlbif;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Page {
@@ -17,8 +25,13 @@ pub enum Page {
     SetObservations,
     ComputeBeliefs,
     ExportJson,
+    ShareLink,
+    ExportImage,
+    ExportDot,
+    Report,
     LoadJson,
     LoadExample,
+    SavedNetworks,
     Help,
 }
 
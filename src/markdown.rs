@@ -69,7 +69,11 @@ where
             Event::Text(text) => add_child!(VText::new(text.to_string()).into()),
             Event::SoftBreak => add_child!(VText::new("\n".to_string()).into()),
             Event::HardBreak => add_child!(VTag::new("br").into()),
-            _ => println!("Unknown event: {:#?}", ev),
+            // raw HTML is shown as text rather than interpreted
+            Event::Html(html) | Event::InlineHtml(html) => {
+                add_child!(VText::new(html.to_string()).into())
+            }
+            _ => {}
         }
     }
 
@@ -114,9 +118,7 @@ where
             };
             el
         }
-        Tag::HtmlBlock => {
-            unimplemented!();
-        }
+        Tag::HtmlBlock => VTag::new("pre"),
         Tag::List(None) => VTag::new("ul"),
         Tag::List(Some(1)) => VTag::new("ol"),
         Tag::List(Some(ref start)) => {
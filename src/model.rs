@@ -1,16 +1,30 @@
+use std::collections::{BTreeMap, VecDeque};
+
 use failure::Error;
 use loopybayesnet::LogProbVector;
 use ndarray::ArrayD;
-use stdweb::{console, web::document};
+use serde::{Deserialize, Serialize};
+use stdweb::{
+    console, js,
+    traits::*,
+    unstable::TryInto,
+    web::{document, event::KeyDownEvent, File},
+};
 use yew::{
     format::Nothing,
-    services::fetch::{FetchService, FetchTask, Request, Response},
+    services::{
+        fetch::{FetchService, FetchTask, Request, Response},
+        reader::{FileData, ReaderService, ReaderTask},
+    },
     Component, ComponentLink, ShouldRender,
 };
 
 use crate::{
-    graph::{DeserError, DAG},
+    draw::{BeliefDisplay, CanvasEvent, GraphvizOptions},
+    formats::Format,
+    graph::{DeserError, ParentRemoval, DAG},
     i18n::Lang,
+    inference::Explanation,
     lang, Page,
 };
 
@@ -18,6 +32,9 @@ use crate::{
 pub enum Msg {
     Ignore,
     AddNode,
+    DelNode {
+        node: usize,
+    },
     SetLabel {
         node: usize,
         label: String,
@@ -30,6 +47,16 @@ pub enum Msg {
         node: usize,
         value_id: usize,
     },
+    RenameValue {
+        node: usize,
+        value_id: usize,
+        name: String,
+    },
+    MoveValue {
+        node: usize,
+        value_id: usize,
+        position: usize,
+    },
     AddParent {
         node: usize,
         parent_id: usize,
@@ -37,7 +64,13 @@ pub enum Msg {
     DelParent {
         node: usize,
         parent_id: usize,
+        removal: ParentRemoval,
     },
+    ReverseParent {
+        node: usize,
+        parent_id: usize,
+    },
+    Canvas(CanvasEvent),
     SetDesc {
         node: usize,
         desc: String,
@@ -46,6 +79,27 @@ pub enum Msg {
         node: usize,
         obs: Option<usize>,
     },
+    SetLikelihood {
+        node: usize,
+        likelihood: Option<Vec<f32>>,
+    },
+    ToggleAllowed {
+        node: usize,
+        value: usize,
+    },
+    SetLikelihoodValue {
+        node: usize,
+        value: usize,
+        credency: f32,
+    },
+    AddScenario(String),
+    ApplyScenario(usize),
+    UpdateScenario(usize),
+    RenameScenario {
+        scenario: usize,
+        name: String,
+    },
+    DelScenario(usize),
     UpdateCredencies {
         node: usize,
         credencies: ArrayD<f32>,
@@ -54,46 +108,466 @@ pub enum Msg {
     MoveToPage(Page),
     Reset,
     LoadJson(String),
+    LoadText(String),
+    ReadFile(File),
+    LoadFile(FileData),
+    Download,
+    DownloadSvg,
+    DownloadPng,
+    SetAnnotateBeliefs(bool),
+    SetBeliefDisplay(BeliefDisplay),
+    SetDotOptions(GraphvizOptions),
+    DownloadDot,
+    DownloadReport,
+    SetFormat(Format),
     LoadExample(String),
     ShowHelp(String),
     SetLogOdds(bool),
     SetLang(String),
+    SetInference(InferenceMethod),
+    SetMaxIterations(usize),
+    SetTolerance(f32),
+    SetExplanationCount(usize),
+    SetHypothesisNode(Option<usize>),
+    ToggleExplainedNode(usize),
+    Undo,
+    Redo,
+    SaveNetwork(String),
+    LoadSaved(String),
+    RenameSaved {
+        from: String,
+        to: String,
+    },
+    DeleteSaved(String),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InferenceMethod {
+    /// Exact inference when the network is small enough, loopy belief propagation otherwise
+    Automatic,
+    LoopyBeliefPropagation,
+    Exact,
+}
+
+/// Outcome of the last run of loopy belief propagation
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LoopyStatus {
+    Converged { iterations: usize },
+    Diverged { iterations: usize, change: f32 },
+}
+
+/// Difference of probabilities above which loopy belief propagation is reported as
+/// disagreeing with exact inference
+const DISCREPANCY_THRESHOLD: f32 = 0.001;
+
+/// Maximum number of states kept in the undo history
+const HISTORY_SIZE: usize = 64;
+
+/// Local storage key of the working network and settings
+const STATE_KEY: &str = "bayesomatic.state";
+/// Local storage key of the networks saved by name
+const SAVED_NETWORKS_KEY: &str = "bayesomatic.saved";
+
+/// Prefix of the URL fragment of shareable links
+const SHARE_LINK_PREFIX: &str = "#net=";
+
+/// The state of the app persisted across page reloads
+#[derive(Serialize, Deserialize)]
+struct StoredState {
+    network: String,
+    name: Option<String>,
+    lang: String,
+    logodds: bool,
 }
 
 pub struct BayesOMatic {
     pub(crate) dag: DAG,
     pub(crate) page: Page,
     pub(crate) load_error: Option<DeserError>,
+    pub(crate) format: Format,
     fetch_service: FetchService,
     task: Option<FetchTask>,
+    reader: ReaderService,
+    reader_task: Option<ReaderTask>,
     link: ComponentLink<BayesOMatic>,
     pub(crate) beliefs: Option<Vec<(LogProbVector, usize)>>,
+    /// The beliefs the network gives without any observation
+    pub(crate) priors: Option<Vec<(LogProbVector, usize)>>,
+    pub(crate) belief_display: BeliefDisplay,
     pub(crate) logodds: bool,
+    pub(crate) annotate_beliefs: bool,
+    pub(crate) dot_options: GraphvizOptions,
+    pub(crate) inference: InferenceMethod,
+    pub(crate) discrepancies: Vec<(usize, f32)>,
+    /// Whether exact inference was run, which is skipped on large networks unless it is
+    /// explicitly selected
+    pub(crate) exact_enabled: bool,
+    pub(crate) max_iterations: usize,
+    pub(crate) tolerance: f32,
+    pub(crate) loopy_status: Option<LoopyStatus>,
+    /// The most probable joint assignments of the explained nodes
+    pub(crate) explanations: Option<Vec<Explanation>>,
+    /// Whether finding the explanations was skipped, as it would take too long
+    pub(crate) explanations_skipped: bool,
+    pub(crate) explanation_count: usize,
+    /// The nodes whose values are explained, all the unobserved ones if empty
+    pub(crate) explained_nodes: Vec<usize>,
+    /// Natural logarithm of the probability of the observations
+    pub(crate) log_evidence: Option<f32>,
+    /// The node whose values are weighted against each other by the observations
+    pub(crate) hypothesis_node: Option<usize>,
+    /// Decimal logarithm of the Bayes factor of the observations for each value of the
+    /// hypothesis node
+    pub(crate) evidence_weights: Option<Vec<f32>>,
+    /// Natural logarithm of the probability of the same observations in each saved
+    /// network, if they can be made in it
+    pub(crate) saved_evidence: Vec<(String, Option<f32>)>,
+    /// The beliefs given the current observations, then given the observations of each
+    /// scenario in turn, exact unless exact inference was skipped
+    pub(crate) scenario_beliefs: Vec<Option<Vec<(LogProbVector, usize)>>>,
     pub help_contents: Option<String>,
     pub(crate) lang: Lang,
+    undo_stack: VecDeque<DAG>,
+    redo_stack: Vec<DAG>,
+    last_edit: Option<(&'static str, usize)>,
+    dirty: bool,
+    /// The local storage keys whose last write failed, because the storage is full or
+    /// disabled
+    pub(crate) storage_errors: Vec<&'static str>,
+    pub(crate) network_name: Option<String>,
+    pub(crate) saved_networks: BTreeMap<String, String>,
 }
 
 impl BayesOMatic {
-    fn compute_beliefs(&mut self) {
-        let (mut bayesnet, mapping) = match self.dag.make_bayesnet() {
-            Ok(v) => v,
-            Err(()) => {
-                // beliefs cannnot be computed,
-                self.beliefs = None;
-                return;
+    /// Records the current state of the DAG in the undo history, before it is modified
+    ///
+    /// Successive edits sharing the same `merge_key` (like typing in a text field) are
+    /// recorded as a single undo step.
+    fn checkpoint(&mut self, merge_key: Option<(&'static str, usize)>) {
+        // every edit of the network goes through here
+        self.dirty = true;
+        if merge_key.is_some() && merge_key == self.last_edit {
+            return;
+        }
+        self.last_edit = merge_key;
+        if self.undo_stack.len() >= HISTORY_SIZE {
+            self.undo_stack.pop_front();
+        }
+        self.undo_stack.push_back(self.dag.clone());
+        self.redo_stack.clear();
+    }
+
+    /// Swaps the current DAG with a state from the undo or redo history
+    fn restore_from_history(&mut self, dag: DAG) -> DAG {
+        let current = std::mem::replace(&mut self.dag, dag);
+        self.last_edit = None;
+        self.dirty = true;
+        // the edited node may no longer exist
+        if let Page::NodeEdit(id) = self.page {
+            if self.dag.get(id).is_none() {
+                self.page = Page::Idle;
             }
+        }
+        if self.shows_beliefs(self.page) {
+            self.compute_beliefs();
+        }
+        current
+    }
+
+    /// Whether `page` displays the beliefs, which must then be kept up to date
+    fn shows_beliefs(&self, page: Page) -> bool {
+        page == Page::ComputeBeliefs
+            || page == Page::Report
+            || (page == Page::ExportImage && self.annotate_beliefs)
+    }
+
+    /// Persists the working network and settings in the local storage of the browser
+    fn autosave(&mut self) {
+        let state = StoredState {
+            network: self.dag.write_json(false),
+            name: self.network_name.clone(),
+            lang: self.lang.name.clone(),
+            logodds: self.logodds,
         };
+        let json = serde_json::to_string(&state).unwrap();
+        self.store(STATE_KEY, &json);
+    }
 
-        for _ in 0..100 {
-            bayesnet.step();
+    /// Writes `json` in the local storage under `key`, keeping track of the failures
+    fn store(&mut self, key: &'static str, json: &str) {
+        self.storage_errors.retain(|&k| k != key);
+        if !crate::storage::store(key, json) {
+            self.storage_errors.push(key);
         }
-        let mut beliefs = bayesnet.beliefs();
+    }
+
+    /// Restores the state persisted by a previous session, if any
+    fn restore(&mut self) {
+        let state = crate::storage::restore(STATE_KEY)
+            .and_then(|json| serde_json::from_str::<StoredState>(&json).ok());
+        if let Some(state) = state {
+            if let Ok(dag) = DAG::from_json(&state.network) {
+                self.dag = dag;
+                self.network_name = state.name;
+            }
+            if let Ok(lang) = Lang::load(&state.lang) {
+                self.lang = lang;
+            }
+            self.logodds = state.logodds;
+        }
+        self.saved_networks = crate::storage::restore(SAVED_NETWORKS_KEY)
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+    }
+
+    fn store_saved_networks(&mut self) {
+        let json = serde_json::to_string(&self.saved_networks).unwrap();
+        self.store(SAVED_NETWORKS_KEY, &json);
+    }
+
+    /// Replaces the working network with the one described by `json`, returns whether it
+    /// succeeded
+    fn load_network(&mut self, json: &str) -> bool {
+        self.replace_network(DAG::from_json(json))
+    }
+
+    fn replace_network(&mut self, network: Result<DAG, DeserError>) -> bool {
+        match network {
+            Ok(dag) => {
+                self.checkpoint(None);
+                self.dag = dag;
+                self.page = Page::Idle;
+                self.load_error = None;
+                true
+            }
+            Err(e) => {
+                self.load_error = Some(e);
+                false
+            }
+        }
+    }
+
+    /// Loads the network encoded in the `#net=` fragment of the page URL, if any
+    ///
+    /// The fragment is then removed from the URL, so that reloading the page does not
+    /// override the edits made since.
+    fn load_link(&mut self) {
+        let hash = document()
+            .location()
+            .and_then(|l| l.hash().ok())
+            .unwrap_or_default();
+        if hash.starts_with(SHARE_LINK_PREFIX) {
+            if self.replace_network(DAG::from_link_fragment(&hash[SHARE_LINK_PREFIX.len()..])) {
+                self.network_name = None;
+                self.dirty = false;
+                self.autosave();
+            } else {
+                self.page = Page::LoadJson;
+            }
+            js! { @(no_return)
+                history.replaceState(null, "", location.pathname + location.search);
+            }
+        }
+    }
+
+    /// URL of the current page, encoding the whole working network
+    pub(crate) fn share_link(&self) -> String {
+        let url: String = js! { return location.origin + location.pathname + location.search; }
+            .try_into()
+            .unwrap_or_default();
+        format!(
+            "{}{}{}",
+            url,
+            SHARE_LINK_PREFIX,
+            self.dag.to_link_fragment()
+        )
+    }
+
+    /// Name of the files the network is exported to, without extension
+    pub(crate) fn file_name(&self) -> String {
+        self.network_name
+            .clone()
+            .unwrap_or_else(|| lang!(self.lang, "default-network-name"))
+    }
+
+    fn loopy_beliefs(&mut self) -> Option<Vec<(LogProbVector, usize)>> {
+        match self.run_loopy(&self.dag) {
+            Some((beliefs, status)) => {
+                self.loopy_status = Some(status);
+                Some(beliefs)
+            }
+            None => {
+                // beliefs cannnot be computed
+                self.loopy_status = None;
+                None
+            }
+        }
+    }
 
+    /// Runs loopy belief propagation on `dag` with the current settings
+    fn run_loopy(&self, dag: &DAG) -> Option<(Vec<(LogProbVector, usize)>, LoopyStatus)> {
+        let (mut bayesnet, mapping) = dag.make_bayesnet().ok()?;
+
+        let mut beliefs = bayesnet.beliefs();
+        let mut status = LoopyStatus::Diverged {
+            iterations: self.max_iterations,
+            change: std::f32::INFINITY,
+        };
+        for i in 1..=self.max_iterations {
+            bayesnet.step();
+            let new_beliefs = bayesnet.beliefs();
+            // largest change of a probability since the previous sweep
+            let change = beliefs
+                .iter()
+                .zip(new_beliefs.iter())
+                .map(|(old, new)| {
+                    (old.as_probabilities() - new.as_probabilities())
+                        .fold(0f32, |max, d| f32::max(max, d.abs()))
+                })
+                .fold(0f32, f32::max);
+            beliefs = new_beliefs;
+            if change < self.tolerance {
+                status = LoopyStatus::Converged { iterations: i };
+                break;
+            }
+            status = LoopyStatus::Diverged {
+                iterations: i,
+                change,
+            };
+        }
         for b in &mut beliefs {
             b.renormalize();
         }
 
-        self.beliefs = Some(beliefs.into_iter().zip(mapping.into_iter()).collect());
+        Some((
+            beliefs.into_iter().zip(mapping.into_iter()).collect(),
+            status,
+        ))
+    }
+
+    fn compute_beliefs(&mut self) {
+        let loopy = self.loopy_beliefs();
+        self.exact_enabled = self.inference == InferenceMethod::Exact
+            || crate::inference::exact_inference_cost(&self.dag)
+                <= crate::inference::EXACT_INFERENCE_BUDGET;
+        let exact = if self.exact_enabled {
+            crate::inference::exact_beliefs(&self.dag).ok()
+        } else {
+            None
+        };
+
+        // report the nodes for which loopy belief propagation is off
+        self.discrepancies = match (&loopy, &exact) {
+            (Some(loopy), Some(exact)) => loopy
+                .iter()
+                .zip(exact.iter())
+                .filter_map(|(&(ref l, id), &(ref e, _))| {
+                    let gap = (l.as_probabilities() - e.as_probabilities())
+                        .fold(0f32, |max, d| f32::max(max, d.abs()));
+                    if gap > DISCREPANCY_THRESHOLD {
+                        Some((id, gap))
+                    } else {
+                        None
+                    }
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        // the scenarios are compared with exact inference when it is run, and with loopy
+        // belief propagation otherwise
+        self.scenario_beliefs = if self.dag.scenarios().is_empty() {
+            Vec::new()
+        } else {
+            let current = if self.exact_enabled {
+                exact.clone()
+            } else {
+                loopy.clone()
+            };
+            let scenarios = (0..self.dag.scenarios().len()).map(|i| {
+                let mut dag = self.dag.clone();
+                dag.apply_scenario(i);
+                if self.exact_enabled {
+                    crate::inference::exact_beliefs(&dag).ok()
+                } else {
+                    self.run_loopy(&dag).map(|(beliefs, _)| beliefs)
+                }
+            });
+            Some(current).into_iter().chain(scenarios).collect()
+        };
+
+        self.beliefs = match self.inference {
+            InferenceMethod::Automatic if self.exact_enabled => exact,
+            InferenceMethod::Automatic | InferenceMethod::LoopyBeliefPropagation => loopy,
+            InferenceMethod::Exact => exact,
+        };
+
+        let mut unobserved = self.dag.clone();
+        unobserved.clear_observations();
+        self.priors = if self.exact_enabled {
+            crate::inference::exact_beliefs(&unobserved).ok()
+        } else {
+            self.run_loopy(&unobserved).map(|(beliefs, _)| beliefs)
+        };
+        self.compute_explanations();
+        self.compute_evidence();
+    }
+
+    fn compute_evidence(&mut self) {
+        if !self.exact_enabled {
+            self.log_evidence = None;
+            self.evidence_weights = None;
+            self.saved_evidence = Vec::new();
+            return;
+        }
+        self.log_evidence = crate::inference::log_evidence(&self.dag).ok();
+        self.compute_evidence_weights();
+        // the same observations, made in the other saved networks
+        let current = self.network_name.clone();
+        self.saved_evidence = self
+            .saved_networks
+            .iter()
+            .filter(|&(name, _)| Some(name) != current.as_ref())
+            .map(|(name, json)| {
+                let log_evidence = DAG::from_json(json).ok().and_then(|mut dag| {
+                    dag.copy_observations(&self.dag).ok()?;
+                    crate::inference::log_evidence(&dag).ok()
+                });
+                (name.clone(), log_evidence)
+            })
+            .collect();
+    }
+
+    fn compute_evidence_weights(&mut self) {
+        let node = self
+            .hypothesis_node
+            .filter(|&id| self.dag.get(id).map_or(false, |n| n.observation.is_none()));
+        self.hypothesis_node = node;
+        self.evidence_weights = node
+            .filter(|_| self.exact_enabled)
+            .and_then(|id| crate::inference::evidence_weights(&self.dag, id).ok());
+    }
+
+    fn compute_explanations(&mut self) {
+        self.explained_nodes
+            .retain(|&id| self.dag.get(id).is_some());
+        self.explanations_skipped = self.exact_enabled
+            && self.inference != InferenceMethod::Exact
+            && crate::inference::explanation_cost(
+                &self.dag,
+                &self.explained_nodes,
+                self.explanation_count,
+            ) > crate::inference::EXACT_INFERENCE_BUDGET;
+        if !self.exact_enabled || self.explanations_skipped {
+            self.explanations = None;
+            return;
+        }
+        self.explanations = crate::inference::most_probable_explanations(
+            &self.dag,
+            &self.explained_nodes,
+            self.explanation_count,
+        )
+        .ok();
     }
 
     fn load_help(&mut self) {
@@ -153,19 +627,73 @@ impl Component for BayesOMatic {
     type Message = Msg;
     type Properties = ();
 
-    fn create(_: Self::Properties, link: ComponentLink<Self>) -> Self {
-        BayesOMatic {
+    fn create(_: Self::Properties, mut link: ComponentLink<Self>) -> Self {
+        // global shortcuts for undo (Ctrl+Z, or Cmd+Z on macOS) and redo (Ctrl+Shift+Z)
+        let history = link.send_back(|redo: bool| if redo { Msg::Redo } else { Msg::Undo });
+        document().add_event_listener(move |evt: KeyDownEvent| {
+            if !(evt.ctrl_key() || evt.meta_key()) || evt.key().to_lowercase() != "z" {
+                return;
+            }
+            // let the browser handle undo inside of text fields
+            let in_text_field: bool = js! {
+                var tag = document.activeElement ? document.activeElement.tagName : "";
+                return tag == "INPUT" || tag == "TEXTAREA";
+            }
+            .try_into()
+            .unwrap_or(false);
+            if !in_text_field {
+                evt.prevent_default();
+                history.emit(evt.shift_key());
+            }
+        });
+
+        let mut app = BayesOMatic {
             dag: DAG::new(),
             page: Page::Idle,
             load_error: None,
+            format: Format::Json,
             fetch_service: FetchService::new(),
             task: None,
+            reader: ReaderService::new(),
+            reader_task: None,
             link,
             beliefs: None,
+            priors: None,
+            belief_display: BeliefDisplay::Bars,
             logodds: true,
+            annotate_beliefs: false,
+            dot_options: GraphvizOptions {
+                values: true,
+                tables: false,
+            },
+            inference: InferenceMethod::Automatic,
+            discrepancies: Vec::new(),
+            exact_enabled: true,
+            max_iterations: 100,
+            tolerance: 1e-4,
+            loopy_status: None,
+            explanations: None,
+            explanations_skipped: false,
+            explanation_count: 5,
+            explained_nodes: Vec::new(),
+            log_evidence: None,
+            hypothesis_node: None,
+            evidence_weights: None,
+            saved_evidence: Vec::new(),
+            scenario_beliefs: Vec::new(),
             help_contents: None,
             lang: Lang::load("en").unwrap(),
-        }
+            undo_stack: VecDeque::new(),
+            redo_stack: Vec::new(),
+            last_edit: None,
+            dirty: false,
+            storage_errors: Vec::new(),
+            network_name: None,
+            saved_networks: BTreeMap::new(),
+        };
+        app.restore();
+        app.load_link();
+        app
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
@@ -173,44 +701,186 @@ impl Component for BayesOMatic {
         match msg {
             Msg::Ignore => {}
             Msg::AddNode => {
+                self.checkpoint(None);
                 let id = self.dag.insert_node();
                 self.dag
                     .set_label(id, lang!(self.lang, "default-node-name", id = id));
                 self.page = Page::NodeEdit(id);
             }
+            Msg::DelNode { node } => {
+                self.checkpoint(None);
+                self.dag.remove_node(node);
+                if self.page == Page::NodeEdit(node) {
+                    self.page = Page::Idle;
+                }
+            }
             Msg::SetLabel { node, label } => {
+                self.checkpoint(Some(("label", node)));
                 self.dag.set_label(node, label);
             }
             Msg::AddValue { node, value } => {
+                self.checkpoint(None);
                 self.dag.add_value(node, value);
             }
             Msg::DelValue { node, value_id } => {
+                self.checkpoint(None);
                 self.dag.remove_value(node, value_id);
             }
+            Msg::RenameValue {
+                node,
+                value_id,
+                name,
+            } => {
+                self.checkpoint(Some(("value-name", node)));
+                self.dag.rename_value(node, value_id, name);
+            }
+            Msg::MoveValue {
+                node,
+                value_id,
+                position,
+            } => {
+                self.checkpoint(None);
+                self.dag.move_value(node, value_id, position);
+            }
             Msg::AddParent { node, parent_id } => {
+                self.checkpoint(None);
                 self.dag.add_edge(node, parent_id).unwrap();
             }
-            Msg::DelParent { node, parent_id } => {
-                self.dag.remove_edge(node, parent_id);
+            Msg::DelParent {
+                node,
+                parent_id,
+                removal,
+            } => {
+                self.checkpoint(None);
+                self.dag.remove_edge(node, parent_id, removal);
+                if self.shows_beliefs(self.page) {
+                    self.compute_beliefs();
+                }
+            }
+            Msg::ReverseParent { node, parent_id } => {
+                self.checkpoint(None);
+                self.dag.reverse_edge(node, parent_id).unwrap();
+            }
+            Msg::Canvas(CanvasEvent::Click { node, shift }) => match self.page {
+                // shift-click toggles the clicked node as a parent of the selected one
+                Page::NodeEdit(selected) if shift && selected != node => {
+                    let is_parent = self
+                        .dag
+                        .get(selected)
+                        .map(|n| n.parents.contains(&node))
+                        .unwrap_or(false);
+                    if is_parent {
+                        self.checkpoint(None);
+                        self.dag
+                            .remove_edge(selected, node, ParentRemoval::Marginalize);
+                    } else if self.dag.check_edge_addition(selected, node).is_ok() {
+                        self.checkpoint(None);
+                        self.dag.add_edge(selected, node).unwrap();
+                    } else {
+                        redraw = false;
+                    }
+                }
+                _ => {
+                    self.page = Page::NodeEdit(node);
+                    self.last_edit = None;
+                }
+            },
+            Msg::Canvas(CanvasEvent::Connect { parent, child }) => {
+                if self.dag.check_edge_addition(child, parent).is_ok() {
+                    self.checkpoint(None);
+                    self.dag.add_edge(child, parent).unwrap();
+                    if self.shows_beliefs(self.page) {
+                        self.compute_beliefs();
+                    }
+                } else {
+                    redraw = false;
+                }
+            }
+            Msg::Canvas(CanvasEvent::Disconnect { parent, child }) => {
+                return self.update(Msg::DelParent {
+                    node: child,
+                    parent_id: parent,
+                    removal: ParentRemoval::Marginalize,
+                });
             }
             Msg::SetDesc { node, desc } => {
+                self.checkpoint(Some(("description", node)));
                 self.dag.set_description(node, desc);
                 redraw = false;
             }
             Msg::SetObs { node, obs } => {
+                self.checkpoint(None);
                 self.dag.set_observation(node, obs);
             }
+            Msg::SetLikelihood { node, likelihood } => {
+                self.checkpoint(None);
+                let _ = self.dag.set_likelihood(node, likelihood);
+            }
+            Msg::ToggleAllowed { node, value } => {
+                let mut allowed = self
+                    .dag
+                    .get(node)
+                    .map(|n| n.allowed_values())
+                    .unwrap_or_default();
+                if value < allowed.len() {
+                    allowed[value] = !allowed[value];
+                    // at least one value must stay possible
+                    if allowed.contains(&true) {
+                        self.checkpoint(None);
+                        let _ = self.dag.set_allowed(node, allowed);
+                    }
+                }
+            }
+            Msg::SetLikelihoodValue {
+                node,
+                value,
+                credency,
+            } => {
+                let likelihood = self
+                    .dag
+                    .get(node)
+                    .and_then(|n| n.likelihood.clone())
+                    .filter(|l| value < l.len());
+                if let Some(mut likelihood) = likelihood {
+                    self.checkpoint(Some(("likelihood", node)));
+                    likelihood[value] = credency;
+                    let _ = self.dag.set_likelihood(node, Some(likelihood));
+                }
+            }
+            Msg::AddScenario(name) => {
+                if !name.is_empty() {
+                    self.checkpoint(None);
+                    self.dag.add_scenario(name);
+                }
+            }
+            Msg::ApplyScenario(scenario) => {
+                self.checkpoint(None);
+                self.dag.apply_scenario(scenario);
+            }
+            Msg::UpdateScenario(scenario) => {
+                self.checkpoint(None);
+                self.dag.update_scenario(scenario);
+            }
+            Msg::RenameScenario { scenario, name } => {
+                self.checkpoint(Some(("scenario-name", scenario)));
+                self.dag.rename_scenario(scenario, name);
+            }
+            Msg::DelScenario(scenario) => {
+                self.checkpoint(None);
+                self.dag.remove_scenario(scenario);
+            }
             Msg::UpdateCredencies {
                 node,
                 credencies,
                 descriptions,
             } => {
+                self.checkpoint(None);
                 self.dag.set_credencies(node, credencies).unwrap();
                 self.dag.set_cred_descriptions(node, descriptions).unwrap();
                 redraw = false;
             }
             Msg::MoveToPage(page) => {
-                if page == Page::ComputeBeliefs {
+                if self.shows_beliefs(page) {
                     self.compute_beliefs();
                 } else if page == Page::Help {
                     if self.help_contents.is_none() {
@@ -219,22 +889,86 @@ impl Component for BayesOMatic {
                 }
                 self.page = page;
                 self.load_error = None;
+                // typing again in the same field after leaving it is a new edit
+                self.last_edit = None;
             }
             Msg::Reset => {
+                self.checkpoint(None);
                 self.dag = DAG::new();
+                self.network_name = None;
                 self.load_error = None;
                 self.page = Page::Idle;
             }
-            Msg::LoadJson(json) => match DAG::from_json(&json) {
-                Ok(dag) => {
-                    self.dag = dag;
-                    self.page = Page::Idle;
-                    self.load_error = None;
+            Msg::LoadJson(json) => {
+                if self.load_network(&json) {
+                    self.network_name = None;
                 }
-                Err(e) => {
-                    self.load_error = Some(e);
+            }
+            Msg::ReadFile(file) => {
+                let callback = self.link.send_back(Msg::LoadFile);
+                self.reader_task = Some(self.reader.read_file(file, callback));
+                // only redraw when reading is finished
+                redraw = false;
+            }
+            Msg::LoadText(text) => {
+                if self.replace_network(self.format.parse(&text)) {
+                    self.network_name = None;
                 }
-            },
+            }
+            Msg::LoadFile(data) => {
+                let text = String::from_utf8_lossy(&data.content);
+                let format = Format::from_file_name(&data.name).unwrap_or(self.format);
+                if self.replace_network(format.parse(&text)) {
+                    let extension = format!(".{}", format.extension());
+                    let name = data.name.trim_end_matches(&extension[..]);
+                    self.network_name = Some(name.into());
+                }
+            }
+            Msg::Download => {
+                let filename = format!("{}.{}", self.file_name(), self.format.extension());
+                let contents = self.format.write(&self.dag);
+                crate::files::download(&filename, self.format.mime_type(), &contents);
+                redraw = false;
+            }
+            Msg::SetFormat(format) => {
+                self.format = format;
+            }
+            Msg::DownloadSvg => {
+                if let Some(svg) = crate::files::export_svg("#canvas svg") {
+                    let filename = format!("{}.svg", self.file_name());
+                    crate::files::download(&filename, "image/svg+xml", &svg);
+                }
+                redraw = false;
+            }
+            Msg::DownloadPng => {
+                if let Some(svg) = crate::files::export_svg("#canvas svg") {
+                    crate::files::download_png(&format!("{}.png", self.file_name()), &svg);
+                }
+                redraw = false;
+            }
+            Msg::SetDotOptions(options) => {
+                self.dot_options = options;
+            }
+            Msg::DownloadDot => {
+                let filename = format!("{}.gv", self.file_name());
+                let dot = crate::draw::graph_to_graphviz(&self.dag, self.dot_options);
+                crate::files::download(&filename, "text/vnd.graphviz", &dot);
+                redraw = false;
+            }
+            Msg::DownloadReport => {
+                let filename = format!("{}.md", self.file_name());
+                crate::files::download(&filename, "text/markdown", &self.make_report());
+                redraw = false;
+            }
+            Msg::SetBeliefDisplay(display) => {
+                self.belief_display = display;
+            }
+            Msg::SetAnnotateBeliefs(annotate) => {
+                self.annotate_beliefs = annotate;
+                if annotate {
+                    self.compute_beliefs();
+                }
+            }
             Msg::LoadExample(name) => {
                 self.load_example(name);
                 // only redraw when loading is finished
@@ -245,15 +979,94 @@ impl Component for BayesOMatic {
             }
             Msg::SetLogOdds(logodds) => {
                 self.logodds = logodds;
+                self.dirty = true;
+            }
+            Msg::SetInference(method) => {
+                self.inference = method;
+                self.compute_beliefs();
+            }
+            Msg::SetMaxIterations(max_iterations) => {
+                self.max_iterations = max_iterations;
+                self.compute_beliefs();
+            }
+            Msg::SetTolerance(tolerance) => {
+                self.tolerance = tolerance;
+                self.compute_beliefs();
+            }
+            Msg::SetExplanationCount(count) => {
+                self.explanation_count = count.max(1).min(crate::inference::MAX_EXPLANATIONS);
+                self.compute_explanations();
+            }
+            Msg::SetHypothesisNode(node) => {
+                self.hypothesis_node = node;
+                self.compute_evidence_weights();
+            }
+            Msg::ToggleExplainedNode(node) => {
+                if self.explained_nodes.contains(&node) {
+                    self.explained_nodes.retain(|&id| id != node);
+                } else {
+                    self.explained_nodes.push(node);
+                }
+                self.compute_explanations();
             }
             Msg::SetLang(lang) => {
                 self.lang = Lang::load(&lang).unwrap();
+                self.dirty = true;
                 // Invalidate the help & reload if relevant
                 self.help_contents = None;
                 if self.page == Page::Help {
                     self.load_help();
                 }
             }
+            Msg::Undo => {
+                if let Some(dag) = self.undo_stack.pop_back() {
+                    let current = self.restore_from_history(dag);
+                    self.redo_stack.push(current);
+                }
+            }
+            Msg::Redo => {
+                if let Some(dag) = self.redo_stack.pop() {
+                    let current = self.restore_from_history(dag);
+                    self.undo_stack.push_back(current);
+                }
+            }
+            Msg::SaveNetwork(name) => {
+                if !name.is_empty() {
+                    self.saved_networks
+                        .insert(name.clone(), self.dag.write_json(false));
+                    self.store_saved_networks();
+                    self.network_name = Some(name);
+                    self.dirty = true;
+                }
+            }
+            Msg::LoadSaved(name) => {
+                if let Some(json) = self.saved_networks.get(&name).cloned() {
+                    if self.load_network(&json) {
+                        self.network_name = Some(name);
+                    }
+                }
+            }
+            Msg::RenameSaved { from, to } => {
+                if !to.is_empty() && !self.saved_networks.contains_key(&to) {
+                    if let Some(json) = self.saved_networks.remove(&from) {
+                        self.saved_networks.insert(to.clone(), json);
+                        self.store_saved_networks();
+                        if self.network_name.as_ref() == Some(&from) {
+                            self.network_name = Some(to);
+                            self.dirty = true;
+                        }
+                    }
+                }
+            }
+            Msg::DeleteSaved(name) => {
+                self.saved_networks.remove(&name);
+                self.store_saved_networks();
+            }
+        }
+
+        if self.dirty {
+            self.dirty = false;
+            self.autosave();
         }
 
         redraw
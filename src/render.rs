@@ -1,11 +1,14 @@
 use stdweb::{
+    js,
     traits::*,
     unstable::TryInto,
     web::{document, html_element::TextAreaElement},
 };
 use yew::{html, html::ChangeData, Html, Renderable};
 
-use crate::draw::DotCanvas;
+use crate::draw::{DotCanvas, GraphvizOptions};
+use crate::editor::fetch_input_and_clear;
+use crate::formats::Format;
 use crate::graph::{DeserError, EdgeError};
 use crate::lang;
 use crate::model::{BayesOMatic, Msg};
@@ -18,9 +21,16 @@ impl BayesOMatic {
             <div id="menu">
             <ul class="blocky">
                 <li><PushButton text={ lang!(self.lang, "reset") } onclick=|_| Msg::Reset /></li>
+                <li><PushButton text={ lang!(self.lang, "undo") } onclick=|_| Msg::Undo /></li>
+                <li><PushButton text={ lang!(self.lang, "redo") } onclick=|_| Msg::Redo /></li>
                 <li><PushButton text={ lang!(self.lang, "export-json") } onclick=|_| Msg::MoveToPage(Page::ExportJson) /></li>
+                <li><PushButton text={ lang!(self.lang, "share-link") } onclick=|_| Msg::MoveToPage(Page::ShareLink) /></li>
+                <li><PushButton text={ lang!(self.lang, "export-image") } onclick=|_| Msg::MoveToPage(Page::ExportImage) /></li>
+                <li><PushButton text={ lang!(self.lang, "export-dot") } onclick=|_| Msg::MoveToPage(Page::ExportDot) /></li>
+                <li><PushButton text={ lang!(self.lang, "report") } onclick=|_| Msg::MoveToPage(Page::Report) /></li>
                 <li><PushButton text={ lang!(self.lang, "load-json") } onclick=|_| Msg::MoveToPage(Page::LoadJson) /></li>
                 <li><PushButton text={ lang!(self.lang, "load-example") } onclick=|_| Msg::MoveToPage(Page::LoadExample) /></li>
+                <li><PushButton text={ lang!(self.lang, "saved-networks") } onclick=|_| Msg::MoveToPage(Page::SavedNetworks) /></li>
                 <li><PushButton text={ lang!(self.lang, "help") } onclick=|_| Msg::MoveToPage(Page::Help) /></li>
                 <li><a href="https:/github.com/vberger/Bayes-O-Matic/">{ lang!(self.lang, "github") }</a></li>
                 <li>{ lang!(self.lang, "language") }
@@ -70,6 +80,24 @@ impl BayesOMatic {
                 DeserError::Graph(EdgeError::WouldCycle) => lang!(self.lang, "err-cycle"),
                 DeserError::Graph(EdgeError::BadNode) => lang!(self.lang, "err-nodenotfound"),
                 DeserError::Graph(EdgeError::AlreadyExisting) => lang!(self.lang, "err-edges"),
+                DeserError::Link => lang!(self.lang, "invalid-share-link"),
+                DeserError::Syntax { line, ref message } => format!(
+                    "{}: {}",
+                    lang!(self.lang, "invalid-syntax", line = *line),
+                    message
+                ),
+                DeserError::Invalid(ref message) => {
+                    format!("{}: {}", lang!(self.lang, "invalid-network"), message)
+                }
+                DeserError::Unsupported {
+                    line,
+                    ref construct,
+                } => lang!(
+                    self.lang,
+                    "unsupported-construct",
+                    line = *line,
+                    construct = &construct[..]
+                ),
             };
             html! {
                 <p class="error">{ text }</p>
@@ -79,13 +107,130 @@ impl BayesOMatic {
         }
     }
 
+    fn make_format_select(&self) -> Html<Self> {
+        html! {
+            <p>{ lang!(self.lang, "file-format") }
+            <select onchange=|v| if let ChangeData::Select(v) = v {
+                Format::from_extension(&v.raw_value()).map(Msg::SetFormat).unwrap_or(Msg::Ignore)
+            } else { Msg::Ignore }>
+                { for Format::ALL.iter().map(|&format| html! {
+                    <option selected={ self.format == format } value={ format.extension() }>{ format.name() }</option>
+                })}
+            </select>
+            </p>
+        }
+    }
+
+    fn saved_networks(&self) -> Html<Self> {
+        let current_name = self.network_name.clone().unwrap_or_default();
+        html! {
+            <div id="popup">
+                { self.print_error() }
+                <p>
+                    { lang!(self.lang, "save-network-as") }
+                    <input name="networkname" size=24 value={ current_name }></input>
+                    <a href="#" onclick=|_| Msg::SaveNetwork(fetch_input_and_clear("networkname"))>{ lang!(self.lang, "save") }</a>
+                </p>
+                { if self.saved_networks.is_empty() { html! {
+                    <p>{ lang!(self.lang, "no-saved-networks") }</p>
+                }} else { html! {} }}
+                <ul class="silentlist widelist">
+                { for self.saved_networks.keys().map(|name| {
+                    let (rename, load, delete) = (name.clone(), name.clone(), name.clone());
+                    let confirmation = lang!(self.lang, "confirm-delete-network", name = &name[..]);
+                    html! {
+                        <li>
+                            <input size=24 value={ name }
+                                   onchange=|v| if let ChangeData::Value(v) = v { Msg::RenameSaved { from: rename.clone(), to: v } } else { Msg::Ignore }>
+                            </input>
+                            <a href="#" onclick=|_| Msg::LoadSaved(load.clone())>{ lang!(self.lang, "load") }</a>
+                            <a href="#" onclick=|_| {
+                                let confirmed: bool = js! { return confirm(@{ &confirmation }); }.try_into().unwrap_or(false);
+                                if confirmed { Msg::DeleteSaved(delete.clone()) } else { Msg::Ignore }
+                            }>{ lang!(self.lang, "delete") }</a>
+                        </li>
+                    }
+                })}
+                </ul>
+                <a href="#" onclick=|_| Msg::MoveToPage(Page::Idle)>{ lang!(self.lang, "close") }</a>
+            </div>
+        }
+    }
+
     fn content(&self) -> Html<Self> {
         match self.page {
             Page::ExportJson => {
                 html! {
                     <div id="popup">
+                        { self.make_format_select() }
+                        <textarea cols=110 rows=20 readonly=true>
+                            { self.format.write(&self.dag) }
+                        </textarea>
+                        <br/>
+                        <a href="#" onclick=|_| Msg::Download>{ lang!(self.lang, "download-file", name = format!("{}.{}", self.file_name(), self.format.extension())) }</a>
+                        <a href="#" onclick=|_| Msg::MoveToPage(Page::Idle)>{ lang!(self.lang, "close") }</a>
+                    </div>
+                }
+            }
+            Page::ExportImage => {
+                let annotate = self.annotate_beliefs;
+                let annotations = self.belief_annotations().filter(|_| annotate);
+                html! {
+                    <div id="popup">
+                        <p>
+                            <input type="checkbox" checked={ annotate }
+                                   onclick=|_| Msg::SetAnnotateBeliefs(!annotate)></input>
+                            { lang!(self.lang, "annotate-beliefs") }
+                        </p>
+                        <DotCanvas graph={ &self.dag } dot={ crate::draw::graph_to_dot(&self.dag, annotations.as_ref()) }
+                                   onevent=|_| Msg::Ignore />
+                        <a href="#" onclick=|_| Msg::DownloadSvg>{ lang!(self.lang, "download-file", name = format!("{}.svg", self.file_name())) }</a>
+                        <a href="#" onclick=|_| Msg::DownloadPng>{ lang!(self.lang, "download-file", name = format!("{}.png", self.file_name())) }</a>
+                        <a href="#" onclick=|_| Msg::MoveToPage(Page::Idle)>{ lang!(self.lang, "close") }</a>
+                    </div>
+                }
+            }
+            Page::ExportDot => {
+                let options = self.dot_options;
+                html! {
+                    <div id="popup">
+                        <p>
+                            <input type="checkbox" checked={ options.values }
+                                   onclick=|_| Msg::SetDotOptions(GraphvizOptions { values: !options.values, ..options })></input>
+                            { lang!(self.lang, "dot-show-values") }
+                        </p>
+                        <p>
+                            <input type="checkbox" checked={ options.tables }
+                                   onclick=|_| Msg::SetDotOptions(GraphvizOptions { tables: !options.tables, ..options })></input>
+                            { lang!(self.lang, "dot-show-tables") }
+                        </p>
                         <textarea cols=110 rows=20 readonly=true>
-                            { self.dag.to_json() }
+                            { crate::draw::graph_to_graphviz(&self.dag, options) }
+                        </textarea>
+                        <br/>
+                        <a href="#" onclick=|_| Msg::DownloadDot>{ lang!(self.lang, "download-file", name = format!("{}.gv", self.file_name())) }</a>
+                        <a href="#" onclick=|_| Msg::MoveToPage(Page::Idle)>{ lang!(self.lang, "close") }</a>
+                    </div>
+                }
+            }
+            Page::Report => {
+                html! {
+                    <div id="popup">
+                        { crate::markdown::render_markdown(&self.make_report()) }
+                        <p class="noprint">
+                            <a href="#" onclick=|_| Msg::DownloadReport>{ lang!(self.lang, "download-file", name = format!("{}.md", self.file_name())) }</a>
+                            <a href="#" onclick=|_| { js! { window.print(); }; Msg::Ignore }>{ lang!(self.lang, "print") }</a>
+                            <a href="#" onclick=|_| Msg::MoveToPage(Page::Idle)>{ lang!(self.lang, "close") }</a>
+                        </p>
+                    </div>
+                }
+            }
+            Page::ShareLink => {
+                html! {
+                    <div id="popup">
+                        <p>{ lang!(self.lang, "share-link-explanation") }</p>
+                        <textarea cols=110 rows=4 readonly=true>
+                            { self.share_link() }
                         </textarea>
                         <br/>
                         <a href="#" onclick=|_| Msg::MoveToPage(Page::Idle)>{ lang!(self.lang, "close") }</a>
@@ -99,12 +244,35 @@ impl BayesOMatic {
                     let input: TextAreaElement = input.try_into().unwrap();
                     input.value()
                 }
+                let accepted_files = Format::ALL
+                    .iter()
+                    .map(|f| format!(".{}", f.extension()))
+                    .collect::<Vec<_>>()
+                    .join(",");
                 html! {
                     <div id="popup">
                         { self.print_error() }
+                        <div class="dropzone"
+                             ondragover=|evt| { evt.prevent_default(); Msg::Ignore }
+                             ondrop=|evt| {
+                                 evt.prevent_default();
+                                 evt.data_transfer()
+                                    .and_then(|data| data.files().iter().next())
+                                    .map(Msg::ReadFile)
+                                    .unwrap_or(Msg::Ignore)
+                             }>
+                            <p>{ lang!(self.lang, "drop-network-file") }</p>
+                            <input type="file" accept={ accepted_files }
+                                   onchange=|v| if let ChangeData::Files(files) = v {
+                                       files.iter().next().map(Msg::ReadFile).unwrap_or(Msg::Ignore)
+                                   } else { Msg::Ignore }>
+                            </input>
+                        </div>
+                        <p>{ lang!(self.lang, "paste-json") }</p>
+                        { self.make_format_select() }
                         <textarea name="loadjson" cols=110 rows=20></textarea>
                         <br/>
-                        <a href="#" onclick=|_| { Msg::LoadJson(fetch_loadjson_contents()) }>{ lang!(self.lang, "load") }</a>
+                        <a href="#" onclick=|_| { Msg::LoadText(fetch_loadjson_contents()) }>{ lang!(self.lang, "load") }</a>
                         <a href="#" onclick=|_| Msg::MoveToPage(Page::Idle)>{ lang!(self.lang, "close") }</a>
                     </div>
                 }
@@ -120,6 +288,7 @@ impl BayesOMatic {
                     </div>
                 }
             }
+            Page::SavedNetworks => self.saved_networks(),
             Page::Help => {
                 if let Some(ref help) = self.help_contents {
                     html! {
@@ -139,7 +308,8 @@ impl BayesOMatic {
             Page::Idle => {
                 html! {
                     <div id="content">
-                        <DotCanvas dot={ crate::draw::graph_to_dot(&self.dag) } />
+                        <DotCanvas graph={ &self.dag } dot={ crate::draw::graph_to_dot(&self.dag, None) }
+                                   onevent=|event| Msg::Canvas(event) />
                         <div id="editor">
                             { self.editorbar() }
                             <div id="node-editor">
@@ -152,7 +322,8 @@ impl BayesOMatic {
             Page::NodeEdit(id) => {
                 html! {
                     <div id="content">
-                        <DotCanvas dot={ crate::draw::graph_to_dot(&self.dag) } />
+                        <DotCanvas graph={ &self.dag } dot={ crate::draw::graph_to_dot(&self.dag, None) } selected={ Some(id) }
+                                   onevent=|event| Msg::Canvas(event) />
                         <div id="editor">
                             { self.editorbar() }
                             { self.make_nodeedit_tab(id) }
@@ -163,7 +334,8 @@ impl BayesOMatic {
             Page::SetObservations => {
                 html! {
                     <div id="content">
-                        <DotCanvas dot={ crate::draw::graph_to_dot(&self.dag) } />
+                        <DotCanvas graph={ &self.dag } dot={ crate::draw::graph_to_dot(&self.dag, None) }
+                                   onevent=|event| Msg::Canvas(event) />
                         <div id="editor">
                             { self.editorbar() }
                             { self.make_observation_tab() }
@@ -174,7 +346,8 @@ impl BayesOMatic {
             Page::ComputeBeliefs => {
                 html! {
                     <div id="content">
-                        <DotCanvas dot={ crate::draw::graph_to_dot(&self.dag) } />
+                        <DotCanvas graph={ &self.dag } dot={ crate::draw::graph_to_dot(&self.dag, self.belief_annotations().as_ref()) }
+                                   onevent=|event| Msg::Canvas(event) />
                         <div id="editor">
                             { self.editorbar() }
                             { self.make_beliefs_tab() }
@@ -191,6 +364,9 @@ impl Renderable<BayesOMatic> for BayesOMatic {
         html! {
             <div id="main">
                 { self.topbar() }
+                { if self.storage_errors.is_empty() { html! {} } else { html! {
+                    <p class="error">{ lang!(self.lang, "storage-error") }</p>
+                }}}
                 { self.content() }
             </div>
         }
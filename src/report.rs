@@ -0,0 +1,164 @@
+use std::fmt::Write;
+
+use ndarray::IxDyn;
+
+use crate::formats::value_combinations;
+use crate::lang;
+use crate::model::BayesOMatic;
+
+/*
+ * Human-readable report of the whole network
+ */
+
+/// Escapes the characters Markdown would interpret in a short piece of text
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '#' | '|' | '<' | '>' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push(' '),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl BayesOMatic {
+    /// Describes the network, node by node, as a Markdown document
+    pub fn make_report(&self) -> String {
+        let mut report = String::new();
+        writeln!(report, "# {}\n", escape(&self.file_name())).unwrap();
+        if self.beliefs.is_none() {
+            writeln!(report, "{}\n", lang!(self.lang, "inference-no-value")).unwrap();
+        }
+
+        for id in self.dag.topological_order() {
+            let node = self.dag.get(id).unwrap();
+            let parents = node
+                .parents
+                .iter()
+                .map(|&p| self.dag.get(p).unwrap())
+                .collect::<Vec<_>>();
+            writeln!(report, "## {}\n", escape(&node.label)).unwrap();
+            if !node.description.trim().is_empty() {
+                writeln!(report, "{}\n", node.description.trim()).unwrap();
+            }
+
+            let values = node.values.iter().map(|v| escape(v)).collect::<Vec<_>>();
+            writeln!(
+                report,
+                "- **{}** {}",
+                lang!(self.lang, "node-values"),
+                values.join(", ")
+            )
+            .unwrap();
+            let parent_names = if parents.is_empty() {
+                lang!(self.lang, "report-no-parents")
+            } else {
+                parents
+                    .iter()
+                    .map(|p| escape(&p.label))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            writeln!(
+                report,
+                "- **{}** {}",
+                lang!(self.lang, "node-parents"),
+                parent_names
+            )
+            .unwrap();
+            if let Some(obs) = node.observation {
+                writeln!(
+                    report,
+                    "- **{}**",
+                    lang!(self.lang, "obs-as", value = &escape(&node.values[obs])[..])
+                )
+                .unwrap();
+            }
+            if let Some(ref allowed) = node.allowed {
+                let excluded = values
+                    .iter()
+                    .zip(allowed)
+                    .filter(|&(_, &a)| !a)
+                    .map(|(v, _)| &v[..])
+                    .collect::<Vec<_>>();
+                writeln!(
+                    report,
+                    "- **{}** {}",
+                    lang!(self.lang, "excluded-values"),
+                    excluded.join(", ")
+                )
+                .unwrap();
+            }
+            if let Some(ref likelihood) = node.likelihood {
+                let credencies = values
+                    .iter()
+                    .zip(likelihood)
+                    .map(|(v, l)| format!("{}: {}", v, l))
+                    .collect::<Vec<_>>();
+                writeln!(
+                    report,
+                    "- **{}** {}",
+                    lang!(self.lang, "likelihood"),
+                    credencies.join(", ")
+                )
+                .unwrap();
+            }
+            let beliefs = self
+                .beliefs
+                .as_ref()
+                .and_then(|b| b.iter().find(|&&(_, i)| i == id));
+            if let Some(&(ref beliefs, _)) = beliefs {
+                let posterior = values
+                    .iter()
+                    .zip(beliefs.log_probabilities())
+                    .map(|(v, b)| format!("{}: {:.1}%", v, b.exp() * 100.0))
+                    .collect::<Vec<_>>();
+                writeln!(
+                    report,
+                    "- **{}** {}",
+                    lang!(self.lang, "report-posterior"),
+                    posterior.join(", ")
+                )
+                .unwrap();
+            }
+            report.push('\n');
+
+            if node.values.is_empty() {
+                continue;
+            }
+            // the probability table, one row per combination of parent values
+            let probabilities = self.dag.probability_table(id).unwrap();
+            let mut header = parents.iter().map(|p| escape(&p.label)).collect::<Vec<_>>();
+            header.extend(values.iter().cloned());
+            header.push(lang!(self.lang, "explanation"));
+            writeln!(report, "| {} |", header.join(" | ")).unwrap();
+            writeln!(report, "|{}", "---|".repeat(header.len())).unwrap();
+            for (row, combination) in value_combinations(&probabilities.shape()[1..]).enumerate() {
+                let mut cells = parents
+                    .iter()
+                    .zip(&combination)
+                    .map(|(p, &v)| escape(&p.values[v]))
+                    .collect::<Vec<_>>();
+                for i in 0..node.values.len() {
+                    let mut index = vec![i];
+                    index.extend(&combination);
+                    cells.push(format!("{:.3}", probabilities[IxDyn(&index)]));
+                }
+                cells.push(
+                    node.cred_description
+                        .get(row)
+                        .map(|d| escape(d.trim()))
+                        .unwrap_or_default(),
+                );
+                writeln!(report, "| {} |", cells.join(" | ")).unwrap();
+            }
+            report.push('\n');
+        }
+        report
+    }
+}
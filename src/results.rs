@@ -1,45 +1,118 @@
 use loopybayesnet::LogProbVector;
-use ndarray::ArrayView1;
 use yew::{html, html::ChangeData, Html};
 
 use crate::{
+    draw::{BeliefAnnotations, BeliefDisplay},
+    editor::fetch_input_and_clear,
+    inference::log_sum_exp_vec,
     lang,
-    model::{BayesOMatic, Msg},
+    model::{BayesOMatic, InferenceMethod, LoopyStatus, Msg},
 };
 
-fn log_sum_exp_vec(x: ArrayView1<f32>) -> f32 {
-    let max_log = x.fold(std::f32::NEG_INFINITY, |old_max, &v| f32::max(old_max, v));
-    if !max_log.is_finite() {
-        // if max_log is +inf, result will be +inf anyway
-        // if max_log is -inf, then all log values are -inf, and the result of the log_sum_exp is too
-        max_log
-    } else {
-        max_log + x.mapv(|v| (v - max_log).exp()).sum().ln()
-    }
-}
+/// Change of probability from the current observations above which the beliefs given
+/// a scenario are highlighted
+const SCENARIO_DELTA_THRESHOLD: f32 = 0.01;
 
 impl BayesOMatic {
-    fn make_observation_select(&self, id: usize, node: &crate::graph::Node) -> Html<Self> {
+    fn make_observation_checklist(&self, id: usize, node: &crate::graph::Node) -> Html<Self> {
+        let count = node.values.len();
+        let uncertain = node.likelihood.is_some();
+        let allowed = node.allowed_values();
+        let values = if uncertain {
+            self.make_likelihood_edit(id, node)
+        } else {
+            html! {
+                <ul class="blocky">
+                    { for node.values.iter().zip(allowed).enumerate().map(|(i, (v, allowed))| {
+                        html! {
+                            <li>
+                                <input type="checkbox" checked={ allowed }
+                                       onclick=|_| Msg::ToggleAllowed { node: id, value: i }></input>
+                                <a href="#" onclick=|_| Msg::SetObs { node: id, obs: Some(i) }>{ v }</a>
+                            </li>
+                        }
+                    })}
+                </ul>
+            }
+        };
         html! {
-            <select onchange=|v| if let ChangeData::Select(v) = v { Msg::SetObs { node: id, obs: v.raw_value().parse().ok() }} else { Msg::Ignore }>
-                <option selected={ node.observation.is_none() } value=""></option>
-                { for node.values.iter().enumerate().map(|(i,v)| {
-                    html! { <option selected={ node.observation == Some(i) } value={ i }>{ v }</option> }
+            <span>
+                { values }
+                <input type="checkbox" checked={ uncertain }
+                       onclick=|_| Msg::SetLikelihood { node: id, likelihood: if uncertain { None } else { Some(vec![0.0; count]) } }></input>
+                { lang!(self.lang, "uncertain-observation") }
+                { if node.observation.is_some() || node.allowed.is_some() || uncertain { html! {
+                    <a href="#" onclick=|_| Msg::SetObs { node: id, obs: None }>{ lang!(self.lang, "clear-observation") }</a>
+                }} else { html! {} }}
+            </span>
+        }
+    }
+
+    fn make_likelihood_edit(&self, id: usize, node: &crate::graph::Node) -> Html<Self> {
+        let likelihood = match node.likelihood {
+            Some(ref likelihood) => likelihood,
+            None => return html! {},
+        };
+        html! {
+            <ul class="blocky">
+                <li>{ lang!(self.lang, "likelihood") }</li>
+                { for node.values.iter().zip(likelihood).enumerate().map(|(i, (v, l))| {
+                    html! {
+                        <li>
+                            { v }{ " " }
+                            <input size=4 value={ l.to_string() }
+                                   onchange=|v| if let ChangeData::Value(v) = v {
+                                       v.parse().map(|credency| Msg::SetLikelihoodValue { node: id, value: i, credency }).unwrap_or(Msg::Ignore)
+                                   } else { Msg::Ignore }>
+                            </input>
+                        </li>
+                    }
                 })}
-            </select>
+            </ul>
+        }
+    }
+
+    fn make_scenario_switcher(&self) -> Html<Self> {
+        let current = self.dag.observations();
+        html! {
+            <div>
+                <p>{ lang!(self.lang, "scenarios") }</p>
+                <ul class="silentlist">
+                    { for self.dag.scenarios().iter().enumerate().map(|(i, scenario)| {
+                        let active = scenario.observations == current;
+                        html! {
+                            <li>
+                                <input size=24 value={ &scenario.name }
+                                       onchange=|v| if let ChangeData::Value(v) = v { Msg::RenameScenario { scenario: i, name: v } } else { Msg::Ignore }>
+                                </input>
+                                <a href="#" class={ if active { "selected" } else { "" } }
+                                   onclick=|_| Msg::ApplyScenario(i)>{ lang!(self.lang, "apply-scenario") }</a>
+                                <a href="#" onclick=|_| Msg::UpdateScenario(i)>{ lang!(self.lang, "update-scenario") }</a>
+                                <a href="#" onclick=|_| Msg::DelScenario(i)>{ lang!(self.lang, "delete") }</a>
+                            </li>
+                        }
+                    })}
+                </ul>
+                <p>
+                    { lang!(self.lang, "save-scenario-as") }
+                    <input name="scenarioname" size=24></input>
+                    <a href="#" onclick=|_| Msg::AddScenario(fetch_input_and_clear("scenarioname"))>{ lang!(self.lang, "save") }</a>
+                </p>
+            </div>
         }
     }
 
     pub fn make_observation_tab(&self) -> Html<Self> {
         html! {
             <div id="node-editor">
+                { self.make_scenario_switcher() }
                 <p>{ lang!(self.lang, "obs-for-nodes") }</p>
                 <ul class="silentlist">
                     { for self.dag.iter_nodes().map(|(id, node)| {
                         html! {
                             <li>
                             { lang!(self.lang, "node", name=&node.label[..]) }
-                            { self.make_observation_select(id, node) }
+                            { self.make_observation_checklist(id, node) }
                             </li>
                         }
                     })}
@@ -48,6 +121,17 @@ impl BayesOMatic {
         }
     }
 
+    fn make_discrepancy_note(&self, nodeid: usize) -> Html<Self> {
+        match self.discrepancies.iter().find(|&&(id, _)| id == nodeid) {
+            Some(&(_, gap)) => html! {
+                <p class="warning">
+                    { lang!(self.lang, "loopy-discrepancy", gap = format!("{:.1}", gap * 100.0)) }
+                </p>
+            },
+            None => html! {},
+        }
+    }
+
     fn make_belief_node(&self, nodeid: usize, beliefs: &LogProbVector) -> Html<Self> {
         let node = self.dag.get(nodeid).unwrap();
         if let Some(obs) = node.observation {
@@ -73,6 +157,7 @@ impl BayesOMatic {
                 html! {
                     <li>
                         <h3>{ lang!(self.lang, "node", name=&node.label[..]) }</h3>
+                        { self.make_discrepancy_note(nodeid) }
                         <ul class="posterior">
                             { for logodds_iter.map(|(name, belief)| {
                                 html! {
@@ -89,6 +174,7 @@ impl BayesOMatic {
                 html! {
                     <li>
                         <h3>{ lang!(self.lang, "node", name=&node.label[..]) }</h3>
+                        { self.make_discrepancy_note(nodeid) }
                         <ul class="posterior">
                             { for raw_iter.map(|(name, belief)| {
                                 html! {
@@ -104,6 +190,284 @@ impl BayesOMatic {
         }
     }
 
+    fn make_loopy_settings(&self) -> Html<Self> {
+        let loopy_used = match self.inference {
+            InferenceMethod::Automatic => !self.exact_enabled,
+            InferenceMethod::LoopyBeliefPropagation => true,
+            InferenceMethod::Exact => false,
+        };
+        if !loopy_used {
+            return html! {};
+        }
+        let status = match self.loopy_status {
+            Some(LoopyStatus::Converged { iterations }) => html! {
+                <p>{ lang!(self.lang, "loopy-converged", iterations = iterations) }</p>
+            },
+            Some(LoopyStatus::Diverged { iterations, change }) => html! {
+                <p class="warning">
+                    { lang!(self.lang, "loopy-diverged", iterations = iterations, change = format!("{:.2e}", change)) }
+                </p>
+            },
+            None => html! {},
+        };
+        html! {
+            <div>
+                <p>{ lang!(self.lang, "max-iterations") }
+                <input size=6 value={ self.max_iterations }
+                       onchange=|v| if let ChangeData::Value(v) = v {
+                           v.parse().map(Msg::SetMaxIterations).unwrap_or(Msg::Ignore)
+                       } else { Msg::Ignore }>
+                </input>
+                { lang!(self.lang, "tolerance") }
+                <input size=6 value={ self.tolerance }
+                       onchange=|v| if let ChangeData::Value(v) = v {
+                           v.parse().map(Msg::SetTolerance).unwrap_or(Msg::Ignore)
+                       } else { Msg::Ignore }>
+                </input>
+                </p>
+                { status }
+                { if self.exact_enabled { html! {} } else { html! {
+                    <p class="warning">{ lang!(self.lang, "exact-skipped") }</p>
+                }}}
+            </div>
+        }
+    }
+
+    fn make_hypothesis_weights(&self) -> Html<Self> {
+        let weights = match (self.hypothesis_node, &self.evidence_weights) {
+            (Some(id), &Some(ref weights)) => {
+                let node = self.dag.get(id).unwrap();
+                html! {
+                    <table>
+                        <tr>
+                            <th>{ lang!(self.lang, "hypothesis-value") }</th>
+                            <th>{ lang!(self.lang, "hypothesis-weight") }</th>
+                        </tr>
+                        { for node.values.iter().zip(weights).map(|(value, weight)| {
+                            html! {
+                                <tr>
+                                    <td>{ value }</td>
+                                    <td>{ format!("{:+.1} dB", weight * 10.0) }</td>
+                                </tr>
+                            }
+                        })}
+                    </table>
+                }
+            }
+            _ => html! {},
+        };
+        let selected = self.hypothesis_node;
+        html! {
+            <div>
+                <p>{ lang!(self.lang, "hypothesis-node") }
+                <select onchange=|v| if let ChangeData::Select(v) = v { Msg::SetHypothesisNode(v.raw_value().parse().ok()) } else { Msg::Ignore }>
+                    <option selected={ selected.is_none() } value=""></option>
+                    { for self.dag.iter_nodes().filter(|(_, node)| node.observation.is_none()).map(|(id, node)| {
+                        html! { <option selected={ selected == Some(id) } value={ id }>{ &node.label }</option> }
+                    })}
+                </select>
+                </p>
+                { weights }
+            </div>
+        }
+    }
+
+    fn make_model_comparison(&self, log_evidence: f32) -> Html<Self> {
+        if self.saved_evidence.is_empty() {
+            return html! {};
+        }
+        let log10 = 10f32.ln();
+        html! {
+            <div>
+                <p>{ lang!(self.lang, "model-comparison") }</p>
+                <table>
+                    <tr>
+                        <th>{ lang!(self.lang, "saved-network") }</th>
+                        <th>{ lang!(self.lang, "model-weight") }</th>
+                    </tr>
+                    { for self.saved_evidence.iter().map(|&(ref name, other)| {
+                        let weight = match other {
+                            Some(other) => format!("{:+.1} dB", (log_evidence - other) / log10 * 10.0),
+                            None => lang!(self.lang, "model-incompatible"),
+                        };
+                        html! {
+                            <tr>
+                                <td>{ name }</td>
+                                <td>{ weight }</td>
+                            </tr>
+                        }
+                    })}
+                </table>
+            </div>
+        }
+    }
+
+    fn make_evidence(&self) -> Html<Self> {
+        let log_evidence = match self.log_evidence {
+            Some(log_evidence) => log_evidence,
+            None => return html! {},
+        };
+        let observed = !self.dag.observations().is_empty();
+        let summary = if !observed {
+            lang!(self.lang, "evidence-none")
+        } else if log_evidence == std::f32::NEG_INFINITY {
+            lang!(self.lang, "evidence-impossible")
+        } else {
+            lang!(
+                self.lang,
+                "evidence-probability",
+                probability = format!("{:.4}", log_evidence.exp()),
+                log = format!("{:.2}", log_evidence / 10f32.ln())
+            )
+        };
+        html! {
+            <div>
+                <h2>{ lang!(self.lang, "evidence") }</h2>
+                <p>{ summary }</p>
+                { if observed && log_evidence.is_finite() {
+                    html! {
+                        <div>
+                            { self.make_hypothesis_weights() }
+                            { self.make_model_comparison(log_evidence) }
+                        </div>
+                    }
+                } else {
+                    html! {}
+                }}
+            </div>
+        }
+    }
+
+    fn make_explanations(&self) -> Html<Self> {
+        let explanations = match self.explanations {
+            Some(ref explanations) => explanations.first().map(|best| (best, explanations)),
+            None if self.explanations_skipped => None,
+            None => return html! {},
+        };
+        let count = self.explanation_count;
+        let table = match explanations {
+            _ if self.explanations_skipped => html! {
+                <p class="warning">{ lang!(self.lang, "explanations-skipped") }</p>
+            },
+            Some((best, explanations)) if !best.values.is_empty() => {
+                let log10 = 10f32.ln();
+                html! {
+                    <table>
+                        <tr>
+                            { for best.values.iter().map(|&(id, _)| {
+                                html! { <th>{ &self.dag.get(id).unwrap().label }</th> }
+                            })}
+                            <th>{ lang!(self.lang, "explanation-log-odds") }</th>
+                        </tr>
+                        { for explanations.iter().map(|explanation| {
+                            html! {
+                                <tr>
+                                    { for explanation.values.iter().map(|&(id, value)| {
+                                        html! { <td>{ &self.dag.get(id).unwrap().values[value] }</td> }
+                                    })}
+                                    <td>{ format!("{:.2}", (explanation.log_probability - best.log_probability) / log10) }</td>
+                                </tr>
+                            }
+                        })}
+                    </table>
+                }
+            }
+            // every node is observed, there is nothing to explain
+            Some(_) => html! {},
+            None => html! { <p>{ lang!(self.lang, "no-explanation") }</p> },
+        };
+        html! {
+            <div>
+                <h2>{ lang!(self.lang, "explanations") }</h2>
+                <p>{ lang!(self.lang, "explanation-count") }
+                <input size=3 value={ count }
+                       onchange=|v| if let ChangeData::Value(v) = v {
+                           v.parse().map(Msg::SetExplanationCount).unwrap_or(Msg::Ignore)
+                       } else { Msg::Ignore }>
+                </input>
+                </p>
+                <p>{ lang!(self.lang, "explained-nodes") }</p>
+                <ul class="blocky vlist">
+                    { for self.dag.iter_nodes().filter(|(_, node)| node.observation.is_none()).map(|(id, node)| {
+                        html! {
+                            <li>
+                                <input type="checkbox" checked={ self.explained_nodes.contains(&id) }
+                                       onclick=|_| Msg::ToggleExplainedNode(id)></input>
+                                { &node.label }
+                            </li>
+                        }
+                    })}
+                </ul>
+                { table }
+            </div>
+        }
+    }
+
+    fn make_scenario_comparison(&self) -> Html<Self> {
+        let (current, scenarios) = match self.scenario_beliefs.split_first() {
+            Some((&Some(ref current), scenarios)) => (current, scenarios),
+            _ => return html! {},
+        };
+        html! {
+            <div>
+                <h2>{ lang!(self.lang, "scenario-comparison") }</h2>
+                <table>
+                    <tr>
+                        <th>{ lang!(self.lang, "scenario-node") }</th>
+                        <th>{ lang!(self.lang, "hypothesis-value") }</th>
+                        <th>{ lang!(self.lang, "current-observations") }</th>
+                        { for self.dag.scenarios().iter().map(|scenario| html! { <th>{ &scenario.name }</th> }) }
+                    </tr>
+                    { for current.iter().flat_map(|&(ref beliefs, id)| {
+                        let node = self.dag.get(id).unwrap();
+                        let current = beliefs.as_probabilities();
+                        node.values.iter().enumerate().map(move |(value, name)| {
+                            let reference = current[value];
+                            html! {
+                                <tr>
+                                    <td>{ if value == 0 { &node.label[..] } else { "" } }</td>
+                                    <td>{ name }</td>
+                                    <td>{ format!("{:.1}%", reference * 100.0) }</td>
+                                    { for scenarios.iter().map(|beliefs| {
+                                        let probability = beliefs
+                                            .as_ref()
+                                            .and_then(|b| b.iter().find(|&&(_, i)| i == id))
+                                            .map(|&(ref b, _)| b.as_probabilities()[value]);
+                                        match probability {
+                                            Some(p) => {
+                                                let delta = p - reference;
+                                                let class = if delta >= SCENARIO_DELTA_THRESHOLD {
+                                                    "increase"
+                                                } else if delta <= -SCENARIO_DELTA_THRESHOLD {
+                                                    "decrease"
+                                                } else {
+                                                    ""
+                                                };
+                                                html! {
+                                                    <td class={ class }>{ format!("{:.1}% ({:+.1})", p * 100.0, delta * 100.0) }</td>
+                                                }
+                                            }
+                                            None => html! { <td>{ "-" }</td> },
+                                        }
+                                    })}
+                                </tr>
+                            }
+                        })
+                    })}
+                </table>
+            </div>
+        }
+    }
+
+    /// The inference results to show on the diagram
+    pub fn belief_annotations(&self) -> Option<BeliefAnnotations> {
+        self.beliefs.as_ref().map(|beliefs| BeliefAnnotations {
+            beliefs,
+            priors: self.priors.as_ref().map(|p| &p[..]),
+            display: self.belief_display,
+        })
+    }
+
     pub fn make_beliefs_tab(&self) -> Html<Self> {
         if let Some(ref results) = self.beliefs {
             html! {
@@ -115,11 +479,41 @@ impl BayesOMatic {
                         <option selected={ !self.logodds } value="false">{ lang!(self.lang, "raw-beliefs") }</option>
                     </select>
                     </p>
+                    <p>{ lang!(self.lang, "inference-method") }
+                    <select onchange=|v| if let ChangeData::Select(v) = v {
+                        match &v.raw_value()[..] {
+                            "exact" => Msg::SetInference(InferenceMethod::Exact),
+                            "loopy" => Msg::SetInference(InferenceMethod::LoopyBeliefPropagation),
+                            _ => Msg::SetInference(InferenceMethod::Automatic),
+                        }
+                    } else { Msg::Ignore }>
+                        <option selected={ self.inference == InferenceMethod::Automatic } value="automatic">{ lang!(self.lang, "automatic-inference") }</option>
+                        <option selected={ self.inference == InferenceMethod::Exact } value="exact">{ lang!(self.lang, "exact-inference") }</option>
+                        <option selected={ self.inference == InferenceMethod::LoopyBeliefPropagation } value="loopy">{ lang!(self.lang, "loopy-inference") }</option>
+                    </select>
+                    </p>
+                    { self.make_loopy_settings() }
+                    <p>{ lang!(self.lang, "belief-display") }
+                    <select onchange=|v| if let ChangeData::Select(v) = v {
+                        match &v.raw_value()[..] {
+                            "bars" => Msg::SetBeliefDisplay(BeliefDisplay::Bars),
+                            "most-likely" => Msg::SetBeliefDisplay(BeliefDisplay::MostLikely),
+                            _ => Msg::SetBeliefDisplay(BeliefDisplay::Hidden),
+                        }
+                    } else { Msg::Ignore }>
+                        <option selected={ self.belief_display == BeliefDisplay::Bars } value="bars">{ lang!(self.lang, "belief-bars") }</option>
+                        <option selected={ self.belief_display == BeliefDisplay::MostLikely } value="most-likely">{ lang!(self.lang, "belief-most-likely") }</option>
+                        <option selected={ self.belief_display == BeliefDisplay::Hidden } value="hidden">{ lang!(self.lang, "belief-hidden") }</option>
+                    </select>
+                    </p>
                     <ul class="silentlist widelist">
                         { for results.iter().map(|&(ref beliefs, id)| {
                             self.make_belief_node(id, beliefs)
                         })}
                     </ul>
+                    { self.make_scenario_comparison() }
+                    { self.make_evidence() }
+                    { self.make_explanations() }
                 </div>
             }
         } else {
@@ -0,0 +1,34 @@
+use stdweb::{js, unstable::TryInto};
+
+/*
+ * Helpers for the local storage of the browser
+ *
+ * The storage may be disabled or full, which must not bring the app down.
+ */
+
+/// Stores `value` under `key`, returns whether it succeeded
+pub fn store(key: &str, value: &str) -> bool {
+    js! {
+        try {
+            localStorage.setItem(@{ key }, @{ value });
+            return true;
+        } catch (e) {
+            return false;
+        }
+    }
+    .try_into()
+    .unwrap_or(false)
+}
+
+/// The value stored under `key`, if there is one and the storage can be read
+pub fn restore(key: &str) -> Option<String> {
+    js! {
+        try {
+            return localStorage.getItem(@{ key });
+        } catch (e) {
+            return null;
+        }
+    }
+    .try_into()
+    .ok()
+}
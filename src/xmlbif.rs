@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+
+use ndarray::IxDyn;
+use xml::common::Position;
+use xmltree::{Element, ParseError, XMLNode};
+
+use crate::formats::{
+    format_probability, make_identifier, probabilities_to_credencies, table_from_columns,
+    value_combinations,
+};
+use crate::graph::{DeserError, DAG};
+
+/*
+ * Import and export of the XMLBIF 0.3 format
+ *
+ * The descriptions of the nodes and their observations are stored as properties of
+ * the variables, and the descriptions of the credencies as comments in the tables,
+ * preceding the probabilities they describe.
+ */
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Makes a text usable inside of an XML comment, which cannot contain "--", by escaping
+/// with a backslash each dash following another one, and the backslashes themselves
+fn escape_comment(text: &str) -> String {
+    let mut escaped = String::new();
+    let mut previous = None;
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '-' if previous == Some('-') => escaped.push_str("\\-"),
+            _ => escaped.push(c),
+        }
+        previous = Some(c);
+    }
+    escaped
+}
+
+/// Reverts `escape_comment`
+fn unescape_comment(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+fn invalid<T>(message: String) -> Result<T, DeserError> {
+    Err(DeserError::Invalid(message))
+}
+
+fn text_of(element: &Element) -> String {
+    element
+        .get_text()
+        .map(|t| t.trim().to_string())
+        .unwrap_or_default()
+}
+
+fn children<'a>(element: &'a Element, name: &'a str) -> impl Iterator<Item = &'a Element> {
+    element
+        .children
+        .iter()
+        .filter_map(XMLNode::as_element)
+        .filter(move |e| e.name.eq_ignore_ascii_case(name))
+}
+
+fn child_text(element: &Element, name: &str) -> Option<String> {
+    children(element, name).next().map(text_of)
+}
+
+/// Splits a property of the form `key = value`
+fn parse_property(property: &str) -> Option<(&str, &str)> {
+    let mut parts = property.splitn(2, '=');
+    match (parts.next(), parts.next()) {
+        (Some(key), Some(value)) => Some((key.trim(), value.trim())),
+        _ => None,
+    }
+}
+
+struct XmlVariable {
+    name: String,
+    label: Option<String>,
+    description: String,
+    values: Vec<String>,
+    observation: Option<String>,
+}
+
+fn parse_variable(element: &Element) -> Result<XmlVariable, DeserError> {
+    let name = match child_text(element, "NAME") {
+        Some(name) => name,
+        None => return invalid("a variable has no name".into()),
+    };
+    match element.attributes.get("TYPE").map(|t| &t[..]) {
+        None | Some("nature") => {}
+        Some(t) => return invalid(format!("variable '{}' is of unsupported type {}", name, t)),
+    }
+    let mut variable = XmlVariable {
+        values: children(element, "OUTCOME").map(text_of).collect(),
+        name,
+        label: None,
+        description: String::new(),
+        observation: None,
+    };
+    for property in children(element, "PROPERTY") {
+        let property = property.get_text().unwrap_or_default();
+        match parse_property(&property) {
+            Some(("label", value)) => variable.label = Some(value.into()),
+            Some(("description", value)) => variable.description = value.into(),
+            Some(("observation", value)) => variable.observation = Some(value.into()),
+            _ => {}
+        }
+    }
+    Ok(variable)
+}
+
+/// Reads the probabilities of a table, along with the comments preceding each
+/// combination of parent values
+fn parse_table(
+    table: &Element,
+    child_values: usize,
+    rows: usize,
+) -> Result<(Vec<f32>, Vec<String>), DeserError> {
+    let mut probabilities = Vec::new();
+    let mut comments = vec![String::new(); rows];
+    for node in &table.children {
+        match node {
+            XMLNode::Text(text) => {
+                for word in text.split_whitespace() {
+                    match word.parse() {
+                        Ok(p) => probabilities.push(p),
+                        Err(_) => return invalid(format!("'{}' is not a probability", word)),
+                    }
+                }
+            }
+            XMLNode::Comment(comment) => {
+                if let Some(row) = comments.get_mut(probabilities.len() / child_values) {
+                    if !row.is_empty() {
+                        row.push('\n');
+                    }
+                    row.push_str(&unescape_comment(comment.trim()));
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok((probabilities, comments))
+}
+
+impl DAG {
+    pub fn from_xmlbif(text: &str) -> Result<DAG, DeserError> {
+        let root = Element::parse(text.as_bytes()).map_err(|e| match e {
+            ParseError::MalformedXml(e) => DeserError::Syntax {
+                line: e.position().row as usize + 1,
+                message: e.msg().into(),
+            },
+            ParseError::CannotParse => DeserError::Invalid("empty document".into()),
+        })?;
+        let network = if root.name.eq_ignore_ascii_case("NETWORK") {
+            &root
+        } else {
+            match children(&root, "NETWORK").next() {
+                Some(network) => network,
+                None => return invalid("no network found".into()),
+            }
+        };
+
+        let variables = children(network, "VARIABLE")
+            .map(parse_variable)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut dag = DAG::new();
+        for variable in &variables {
+            let id = dag.insert_node();
+            dag.set_label(
+                id,
+                variable
+                    .label
+                    .clone()
+                    .unwrap_or_else(|| variable.name.clone()),
+            );
+            dag.set_description(id, variable.description.clone());
+            for value in &variable.values {
+                dag.add_value(id, value.clone());
+            }
+            let observation = variable
+                .observation
+                .as_ref()
+                .and_then(|o| variable.values.iter().position(|v| v == o));
+            dag.set_observation(id, observation);
+        }
+
+        let find = |name: &str| match variables.iter().position(|v| v.name == name) {
+            Some(id) => Ok(id),
+            None => invalid(format!("unknown variable '{}'", name)),
+        };
+        for definition in children(network, "DEFINITION") {
+            let child = match child_text(definition, "FOR") {
+                Some(name) => find(&name)?,
+                None => return invalid("a definition is not about any variable".into()),
+            };
+            let parents = children(definition, "GIVEN")
+                .map(|given| find(&text_of(given)))
+                .collect::<Result<Vec<_>, _>>()?;
+            for &parent in &parents {
+                dag.add_edge(child, parent).map_err(DeserError::Graph)?;
+            }
+
+            if variables[child].values.is_empty() {
+                return invalid(format!(
+                    "variable '{}' has no values",
+                    variables[child].name
+                ));
+            }
+            let mut shape = vec![variables[child].values.len()];
+            shape.extend(parents.iter().map(|&p| variables[p].values.len()));
+            let rows = shape[1..].iter().product::<usize>();
+            let (probabilities, comments) = match children(definition, "TABLE").next() {
+                Some(table) => parse_table(table, shape[0], rows)?,
+                None => return invalid(format!("no table for '{}'", variables[child].name)),
+            };
+            if probabilities.len() != shape.iter().product::<usize>() {
+                return invalid(format!(
+                    "wrong number of table values for '{}'",
+                    variables[child].name
+                ));
+            }
+            let table = table_from_columns(&shape, &probabilities);
+            let _ = dag.set_credencies(child, probabilities_to_credencies(&table));
+            let _ = dag.set_cred_descriptions(child, comments);
+        }
+        Ok(dag)
+    }
+
+    pub fn to_xmlbif(&self) -> String {
+        let order = self.topological_order();
+        let mut taken = Vec::new();
+        let names = self
+            .iter_nodes()
+            .map(|(id, node)| (id, make_identifier(&node.label, "node", &mut taken)))
+            .collect::<HashMap<_, _>>();
+
+        let mut xml = String::from("<?xml version=\"1.0\"?>\n<BIF VERSION=\"0.3\">\n<NETWORK>\n");
+        xml += "<NAME>unknown</NAME>\n";
+        for &id in &order {
+            let node = self.get(id).unwrap();
+            xml += "<VARIABLE TYPE=\"nature\">\n";
+            xml += &format!("  <NAME>{}</NAME>\n", escape(&names[&id]));
+            for value in &node.values {
+                xml += &format!("  <OUTCOME>{}</OUTCOME>\n", escape(value));
+            }
+            if node.label != names[&id] {
+                xml += &format!("  <PROPERTY>label = {}</PROPERTY>\n", escape(&node.label));
+            }
+            if !node.description.is_empty() {
+                xml += &format!(
+                    "  <PROPERTY>description = {}</PROPERTY>\n",
+                    escape(&node.description)
+                );
+            }
+            if let Some(obs) = node.observation {
+                xml += &format!(
+                    "  <PROPERTY>observation = {}</PROPERTY>\n",
+                    escape(&node.values[obs])
+                );
+            }
+            xml += "</VARIABLE>\n";
+        }
+        for &id in &order {
+            let node = self.get(id).unwrap();
+            xml += "<DEFINITION>\n";
+            xml += &format!("  <FOR>{}</FOR>\n", escape(&names[&id]));
+            for parent in &node.parents {
+                xml += &format!("  <GIVEN>{}</GIVEN>\n", escape(&names[parent]));
+            }
+            let probabilities = self.probability_table(id).unwrap();
+            xml += "  <TABLE>\n";
+            for (row, combination) in value_combinations(&probabilities.shape()[1..]).enumerate() {
+                let description = node.cred_description.get(row).map(|d| d.trim());
+                if let Some(description) = description.filter(|d| !d.is_empty()) {
+                    xml += &format!("    <!-- {} -->\n", escape_comment(description));
+                }
+                let column = (0..node.values.len())
+                    .map(|i| {
+                        let mut index = vec![i];
+                        index.extend(&combination);
+                        format_probability(probabilities[IxDyn(&index)])
+                    })
+                    .collect::<Vec<_>>();
+                xml += &format!("    {}\n", column.join(" "));
+            }
+            xml += "  </TABLE>\n";
+            xml += "</DEFINITION>\n";
+        }
+        xml += "</NETWORK>\n</BIF>\n";
+        xml
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::tests::{assert_same_network, EXAMPLES};
+
+    #[test]
+    fn round_trip_examples() {
+        for json in EXAMPLES {
+            let dag = DAG::from_json(json).unwrap();
+            let copy = DAG::from_xmlbif(&dag.to_xmlbif()).unwrap();
+            assert_same_network(&dag, &copy, true);
+            for (_, node) in dag.iter_nodes() {
+                let (_, other) = copy
+                    .iter_nodes()
+                    .find(|(_, n)| n.label == node.label)
+                    .unwrap();
+                assert_eq!(node.description, other.description);
+                assert_eq!(node.observation, other.observation);
+                let descriptions = |n: &crate::graph::Node| {
+                    n.cred_description
+                        .iter()
+                        .map(|d| d.trim().to_string())
+                        .collect::<Vec<_>>()
+                };
+                assert_eq!(descriptions(node), descriptions(other));
+            }
+        }
+    }
+
+    #[test]
+    fn dashes_in_descriptions() {
+        let mut dag = DAG::new();
+        let node = dag.insert_node();
+        dag.set_label(node, "A".into());
+        dag.add_value(node, "x".into());
+        dag.add_value(node, "y".into());
+        let description = "Intro\n---\nmore -- \\-- end-";
+        dag.set_cred_descriptions(node, vec![description.into()])
+            .unwrap();
+        let xml = dag.to_xmlbif();
+        let comment = &xml[xml.find("<!--").unwrap() + 4..xml.find("-->").unwrap()];
+        assert!(!comment.contains("--"));
+        let copy = DAG::from_xmlbif(&xml).unwrap();
+        assert_eq!(
+            copy.get(0).unwrap().cred_description,
+            vec![description.to_string()]
+        );
+    }
+}